@@ -1,9 +1,108 @@
 import initModule, { type InitOutput } from "../pkg/any_store";
+import { RowRef } from "./RowRef";
 import { Table } from "./Table";
-import type { Blob, ColMap, F64, I32, Null, Something, String } from "./types";
+import type {
+  AggregateKind,
+  AutoKeyMode,
+  Blob,
+  Change,
+  ChangeKind,
+  ColMap,
+  Comparison,
+  F64,
+  I32,
+  JoinOn,
+  Null,
+  OnDelete,
+  Predicate,
+  QueryOptions,
+  Something,
+  String,
+  TableOptions,
+} from "./types";
 
 const jsStack: any[] = [];
 
+/**
+ * Status returned by the wasm module when a table or row does not exist,
+ * it is not an error, reads return null and writes are ignored
+ */
+const NOT_FOUND = -1;
+const TIMED_OUT = -9;
+const VERSION_CONFLICT = -4;
+
+/**
+ * Tags of values returned by the wasm module, see value.rs
+ */
+const keyTags: Record<number, Something["tag"]> = {
+  0: "i32",
+  1: "string",
+  2: "null",
+  3: "f64",
+  4: "ref",
+  6: "blob",
+};
+
+const changeKinds: ChangeKind[] = [
+  "created",
+  "written",
+  "deleted",
+  "cleared",
+  "overflowed",
+];
+
+const onDeleteModes: Record<OnDelete, number> = {
+  cascade: 0,
+  restrict: 1,
+  setNull: 2,
+};
+
+const aggregateKinds: Record<AggregateKind, number> = {
+  count: 0,
+  sum: 1,
+  min: 2,
+  max: 3,
+  avg: 4,
+};
+
+const comparisons: Record<Comparison, number> = {
+  "==": 0,
+  "!=": 1,
+  "<": 2,
+  "<=": 3,
+  ">": 4,
+  ">=": 5,
+};
+
+/**
+ * Maps a column name to its index and tag
+ */
+export type ColumnResolver = (col: string) => [number, Something["tag"]];
+
+const errorMessages: Record<number, string> = {
+  [-2]: "Stale row handle, the row was removed when its table was cleared",
+  [-3]: "The operation does not apply to the type of the column",
+  [-4]: "The row was written since the version that was read",
+  [-5]: "The row is still referenced by a row with a restrict reference",
+  [-6]: "Another row already holds the value in a unique column",
+  [-7]: "This thread registered more listeners than there are ids, detach it to start over",
+  [-8]: "Waiting would block the main thread or wait forever while holding the lock",
+  [-10]: "Unknown mode or kind, or a predicate is missing an operand",
+};
+
+export class AnyStoreError extends Error {
+  constructor(public readonly code: number) {
+    super(errorMessages[code] ?? `AnyStore error code ${code}`);
+  }
+}
+
+function checkStatus(status: number): number {
+  if (status < NOT_FOUND) {
+    throw new AnyStoreError(status);
+  }
+  return status;
+}
+
 function pushToStringStack(str: string) {
   jsStack.push(str);
 }
@@ -18,12 +117,95 @@ function getWholeStack(): any[] {
 
 function popObjectFromStack(): any {
   const val = jsStack.pop();
+  if (val instanceof RowRef) {
+    return val;
+  }
   if (val && typeof val === "object") {
     return val.value;
   }
   return val;
 }
 
+/**
+ * Reads a value written by `Something::encode` at `offset`,
+ * returns the value and the offset after it
+ */
+function decodeSomething(view: DataView, offset: number): [Something, number] {
+  const tag = keyTags[view.getUint8(offset)];
+  offset += 1;
+  switch (tag) {
+    case "i32":
+      return [{ tag, value: view.getInt32(offset, true) }, offset + 4];
+    case "f64":
+      return [{ tag, value: view.getFloat64(offset, true) }, offset + 8];
+    case "ref": {
+      const table = view.getUint32(offset, true);
+      const row = view.getUint32(offset + 4, true);
+      return [{ tag, value: new RowRef(table, row) }, offset + 8];
+    }
+    case "string":
+    case "blob": {
+      const len = view.getUint32(offset, true);
+      const start = view.byteOffset + offset + 4;
+      const bytes = new Uint8Array(view.buffer.slice(start, start + len));
+      const value = tag === "string" ? bytesToString(bytes) : bytes;
+      return [{ tag, value } as Something, offset + 4 + len];
+    }
+    default:
+      return [{ tag: "null", value: null }, offset];
+  }
+}
+
+/**
+ * One char per byte like `js_push_to_string`, in chunks so that long values
+ * do not exceed the argument limit of `String.fromCharCode`
+ */
+function bytesToString(bytes: Uint8Array): string {
+  const chunkSize = 0x2000;
+  let result = "";
+  for (let start = 0; start < bytes.length; start += chunkSize) {
+    result += String.fromCharCode(...bytes.subarray(start, start + chunkSize));
+  }
+  return result;
+}
+
+/**
+ * Decodes the buffer returned by `db_take_changes`, see `Change::encode`
+ */
+function decodeChanges(buf: Uint8Array, count: number): Change[] {
+  const view = new DataView(buf.buffer, buf.byteOffset, buf.byteLength);
+  const changes: Change[] = [];
+  let offset = 0;
+  for (let i = 0; i < count; i++) {
+    const table = view.getUint32(offset, true);
+    const row = view.getUint32(offset + 4, true);
+    const kind = changeKinds[view.getUint8(offset + 8)];
+    const nCols = view.getUint32(offset + 9, true);
+    offset += 13;
+    const cols: number[] = [];
+    for (let c = 0; c < nCols; c++) {
+      cols.push(view.getUint32(offset, true));
+      offset += 4;
+    }
+    const change: Change = { table, row, kind, cols };
+    const hasValues = view.getUint8(offset) === 1;
+    offset += 1;
+    if (hasValues) {
+      change.old = [];
+      change.new = [];
+      for (let c = 0; c < nCols; c++) {
+        let value: Something;
+        [value, offset] = decodeSomething(view, offset);
+        change.old.push(value);
+        [value, offset] = decodeSomething(view, offset);
+        change.new.push(value);
+      }
+    }
+    changes.push(change);
+  }
+  return changes;
+}
+
 function js_push_null(): void {
   jsStack.push(null);
 }
@@ -36,6 +218,10 @@ function js_put_f64(value: number): void {
   jsStack.push(value);
 }
 
+function js_put_row_ref(table: number, row: number): void {
+  jsStack.push(new RowRef(table, row));
+}
+
 function js_push_string_to_stack() {
   jsStack.push("");
 }
@@ -90,6 +276,7 @@ function startWorkerID(workerID: number) {
 const ops = {
   js_put_i32,
   js_put_f64,
+  js_put_row_ref,
   js_push_to_string,
   js_read_string_length,
   js_read_string,
@@ -118,6 +305,10 @@ export class AnyStore {
   private ops: Ops;
   private listeners: Map<number, () => void> = new Map();
   private workerID: number = 0;
+  /**
+   * Value of the notification counter when `notifyAll` last ran
+   */
+  private seenNotifications: number = 0;
 
   constructor(
     out: InitOutput,
@@ -144,6 +335,17 @@ export class AnyStore {
     return this.ops.exports.table_create_row(tableID);
   }
 
+  createAutoRow(tableID: number): { rowID: number; key: Something } {
+    const rowID = this.ops.exports.table_create_auto_row(tableID);
+    if (rowID === -1) {
+      throw new Error("Table was not created with an auto key mode");
+    }
+    const value = popObjectFromStack();
+    const key: Something =
+      typeof value === "number" ? AnyStore.i32(value) : AnyStore.blob(value);
+    return { rowID, key };
+  }
+
   withBatch<T>(fn: () => T): T {
     try {
       this.ops.exports.start_batch();
@@ -209,7 +411,23 @@ export class AnyStore {
   }
 
   clearTable(tableID: number) {
-    this.ops.exports.table_clear(tableID);
+    checkStatus(this.ops.exports.table_clear(tableID));
+  }
+
+  addReference(
+    fromTable: number,
+    fromCol: number,
+    toTable: number,
+    onDelete: OnDelete,
+  ) {
+    checkStatus(
+      this.ops.exports.table_add_reference(
+        fromTable,
+        fromCol,
+        toTable,
+        onDeleteModes[onDelete],
+      ),
+    );
   }
 
   withColsEqual(
@@ -223,21 +441,213 @@ export class AnyStore {
     return getWholeStack();
   }
 
+  /**
+   * Aggregates a column in a single pass inside the store,
+   * optionally only over the rows where filter.col is equal to filter.value
+   */
+  aggregate(
+    tableID: number,
+    col: number,
+    kind: AggregateKind,
+    filter?: { col: number; value: unknown; tag: Something["tag"] },
+  ): Something["value"] | null {
+    const kindID = aggregateKinds[kind];
+    if (filter) {
+      this.ops.putSomethingOnStack(filter.value, filter.tag);
+      checkStatus(
+        this.ops.exports.table_aggregate_where(tableID, col, kindID, filter.col),
+      );
+    } else {
+      checkStatus(this.ops.exports.table_aggregate(tableID, col, kindID));
+    }
+    return popObjectFromStack() ?? null;
+  }
+
+  /**
+   * Buckets the rows by the value of groupCol and aggregates col in every bucket
+   */
+  groupBy<K extends AggregateKind>(
+    tableID: number,
+    groupCol: number,
+    col: number,
+    kinds: K[],
+  ): ({ group: Something["value"] | null } & Record<K, Something["value"] | null>)[] {
+    kinds.forEach((kind) => this.ops.somethingPushi32ToStack(aggregateKinds[kind]));
+    const count = checkStatus(
+      this.ops.exports.table_group_by(tableID, groupCol, col, kinds.length),
+    );
+    const width = kinds.length + 1;
+    const values = getWholeStack();
+    return Array.from({ length: count }, (_, i) => {
+      const group: any = { group: values[i * width] };
+      kinds.forEach((kind, k) => {
+        group[kind] = values[i * width + 1 + k];
+      });
+      return group;
+    });
+  }
+
+  /**
+   * Returns the ids of the rows matching the predicate,
+   * sorted by options.orderBy or by row id
+   */
+  query(
+    tableID: number,
+    predicate: Predicate | null,
+    resolve: ColumnResolver,
+    options: QueryOptions = {},
+  ): number[] {
+    if (predicate) {
+      this.pushPredicate(predicate, resolve);
+    }
+    options.orderBy?.forEach(({ col, desc }) => {
+      const [index] = resolve(col as string);
+      this.ops.exports.query_order_by(index, desc ?? false);
+    });
+    checkStatus(
+      this.ops.exports.table_query(
+        tableID,
+        options.limit ?? -1,
+        options.offset ?? 0,
+      ),
+    );
+    return getWholeStack();
+  }
+
+  deleteWhere(
+    tableID: number,
+    predicate: Predicate | null,
+    resolve: ColumnResolver,
+  ): number {
+    if (predicate) {
+      this.pushPredicate(predicate, resolve);
+    }
+    return checkStatus(this.ops.exports.table_delete_where(tableID));
+  }
+
+  updateWhere(
+    tableID: number,
+    predicate: Predicate | null,
+    resolve: ColumnResolver,
+    values: { col: number; value: unknown; tag: Something["tag"] }[],
+  ): number {
+    if (predicate) {
+      this.pushPredicate(predicate, resolve);
+    }
+    values.forEach(({ col, value, tag }) => {
+      this.ops.somethingPushi32ToStack(col);
+      this.ops.putSomethingOnStack(value, tag);
+    });
+    return checkStatus(
+      this.ops.exports.table_update_where(tableID, values.length),
+    );
+  }
+
+  /**
+   * Returns the matching `[leftRowID, rightRowID]` pairs,
+   * or the given columns of both rows when columns are passed
+   */
+  join(
+    left: number,
+    leftCol: number,
+    right: number,
+    on: JoinOn,
+    resolveRight: ColumnResolver,
+    columns?: { left: number[]; right: number[] },
+  ): any[][] {
+    const mode = on === "rowID" ? 1 : on === "key" ? 2 : 0;
+    const rightCol =
+      typeof on === "object" ? resolveRight(on.col as string)[0] : 0;
+    if (!columns) {
+      const count = checkStatus(
+        this.ops.exports.table_join(left, leftCol, right, mode, rightCol),
+      );
+      const ids = getWholeStack();
+      return Array.from({ length: count }, (_, i) => ids.slice(i * 2, i * 2 + 2));
+    }
+    columns.left.forEach((col) => this.ops.somethingPushi32ToStack(col));
+    columns.right.forEach((col) => this.ops.somethingPushi32ToStack(col));
+    const count = checkStatus(
+      this.ops.exports.table_join_columns(
+        left,
+        leftCol,
+        right,
+        mode,
+        rightCol,
+        columns.left.length,
+        columns.right.length,
+      ),
+    );
+    const width = columns.left.length + columns.right.length;
+    const values = getWholeStack();
+    return Array.from({ length: count }, (_, i) =>
+      values.slice(i * width, (i + 1) * width),
+    );
+  }
+
+  createIndex(tableID: number, col: number) {
+    checkStatus(this.ops.exports.table_create_index(tableID, col));
+  }
+
+  addUnique(tableID: number, col: number) {
+    checkStatus(this.ops.exports.table_add_unique(tableID, col));
+  }
+
+  private pushPredicate(predicate: Predicate, resolve: ColumnResolver) {
+    const exports = this.ops.exports;
+    if ("and" in predicate) {
+      predicate.and.forEach((p) => this.pushPredicate(p, resolve));
+      exports.predicate_and(predicate.and.length);
+      return;
+    }
+    if ("or" in predicate) {
+      predicate.or.forEach((p) => this.pushPredicate(p, resolve));
+      exports.predicate_or(predicate.or.length);
+      return;
+    }
+    if ("not" in predicate) {
+      this.pushPredicate(predicate.not, resolve);
+      exports.predicate_not();
+      return;
+    }
+    const [col, tag] = resolve(predicate.col as string);
+    if ("in" in predicate) {
+      predicate.in.forEach((value) => this.ops.putSomethingOnStack(value, tag));
+      exports.predicate_in(col, predicate.in.length);
+    } else if ("isNull" in predicate) {
+      exports.predicate_is_null(col);
+    } else if ("prefix" in predicate) {
+      this.ops.putSomethingOnStack(predicate.prefix, tag);
+      exports.predicate_prefix(col);
+    } else {
+      this.ops.putSomethingOnStack(predicate.value, tag);
+      exports.predicate_compare(col, comparisons[predicate.op]);
+    }
+  }
+
   getTable<T extends ColMap>(name: string, colMap: T): Table<T> | null {
     const id = this.ops.getTableIDFromName(name);
     if (!id) {
       return null;
     }
-    return new Table<T>(colMap, id, this);
+    const table = new Table<T>(colMap, id, this);
+    RowRef.tables.set(id, table);
+    return table;
   }
 
   memSize() {
     return 0;
   }
 
-  createTable<T extends ColMap>(name: string, colMap: T): Table<T> {
-    const id = this.ops.createTable(name);
-    return new Table<T>(colMap, id, this);
+  createTable<T extends ColMap>(
+    name: string,
+    colMap: T,
+    options: TableOptions = {},
+  ): Table<T> {
+    const id = this.ops.createTable(name, options.autoKey ?? "none");
+    const table = new Table<T>(colMap, id, this);
+    RowRef.tables.set(id, table);
+    return table;
   }
 
   getRowID(tableID: number, key: Something): number | null {
@@ -257,14 +667,152 @@ export class AnyStore {
     this.ops.tableInsert(tableID, col, row_id);
   }
 
-  addListenerToRow(tableID: number, rowID: number, fn: () => void) {
-    const result = this.ops.addListenerToRow(tableID, rowID);
+  /**
+   * Writes several columns of a row with a single call,
+   * listeners of the row are notified once
+   */
+  insertManyOnTable(
+    tableID: number,
+    rowID: number,
+    values: { col: number; value: unknown; tag: Something["tag"] }[],
+  ) {
+    values.forEach(({ col, value, tag }) => {
+      this.ops.somethingPushi32ToStack(col);
+      this.ops.putSomethingOnStack(value, tag);
+    });
+    checkStatus(
+      this.ops.exports.table_insert_many(tableID, rowID, values.length),
+    );
+  }
+
+  /**
+   * With `cols` the listener is only called for writes to those columns
+   */
+  addListenerToRow(
+    tableID: number,
+    rowID: number,
+    fn: () => void,
+    cols?: number[],
+  ) {
+    let result: number;
+    if (cols) {
+      cols.forEach((col) => this.ops.somethingPushi32ToStack(col));
+      result = checkStatus(
+        this.ops.exports.table_add_column_listener_to_row(
+          tableID,
+          rowID,
+          cols.length,
+        ),
+      );
+    } else {
+      result = this.ops.addListenerToRow(tableID, rowID);
+    }
     this.listeners.set(result, fn);
     return result;
   }
 
+  addTableListener(tableID: number, fn: () => void) {
+    const listenerID = checkStatus(
+      this.ops.exports.table_add_table_listener(tableID),
+    );
+    this.listeners.set(listenerID, fn);
+    return listenerID;
+  }
+
+  removeTableListener(tableID: number, listenerID: number) {
+    this.listeners.delete(listenerID);
+    checkStatus(this.ops.exports.table_remove_table_listener(tableID, listenerID));
+  }
+
+  addKeyListener(tableID: number, key: Something, fn: () => void) {
+    this.ops.putSomethingOnStack(key.value, key.tag);
+    const listenerID = checkStatus(
+      this.ops.exports.table_add_key_listener(tableID),
+    );
+    this.listeners.set(listenerID, fn);
+    return listenerID;
+  }
+
+  removeKeyListener(tableID: number, key: Something, listenerID: number) {
+    this.listeners.delete(listenerID);
+    this.ops.putSomethingOnStack(key.value, key.tag);
+    checkStatus(
+      this.ops.exports.table_remove_key_listener(tableID, listenerID),
+    );
+  }
+
+  /**
+   * Registers a live query, `fn` is called by `notifyAll` with the rows
+   * that entered and left the result since the previous call
+   */
+  subscribe(
+    tableID: number,
+    predicate: Predicate | null,
+    resolve: ColumnResolver,
+    fn: (changes: { added: number[]; removed: number[] }) => void,
+  ): { listenerID: number; rows: number[] } {
+    if (predicate) {
+      this.pushPredicate(predicate, resolve);
+    }
+    const listenerID = checkStatus(this.ops.exports.table_subscribe(tableID));
+    const rows = getWholeStack();
+    this.listeners.set(listenerID, () => {
+      const added = checkStatus(
+        this.ops.exports.table_take_live_query_changes(tableID, listenerID),
+      );
+      const ids = getWholeStack();
+      fn({ added: ids.slice(0, added), removed: ids.slice(added) });
+    });
+    return { listenerID, rows };
+  }
+
+  unsubscribe(tableID: number, listenerID: number) {
+    this.listeners.delete(listenerID);
+    checkStatus(this.ops.exports.table_unsubscribe(tableID, listenerID));
+  }
+
+  /**
+   * This worker receives every change to the table through `takeChanges`,
+   * with the old and new values of the written columns when `withValues` is set
+   */
+  enableChangeFeed(tableID: number, withValues: boolean) {
+    checkStatus(this.ops.exports.table_enable_change_feed(tableID, withValues));
+  }
+
+  disableChangeFeed(tableID: number) {
+    checkStatus(this.ops.exports.table_disable_change_feed(tableID));
+  }
+
+  /**
+   * Changes since the previous call on every table this worker has a feed on
+   */
+  takeChanges(): Change[] {
+    const count = this.ops.exports.db_take_changes();
+    return decodeChanges(popObjectFromStack(), count);
+  }
+
+  /**
+   * Counter in shared memory bumped whenever a listener of this thread is queued
+   */
+  private notificationCounter(): Int32Array {
+    const pointer = this.ops.exports.notification_pointer();
+    return new Int32Array(this.memory.buffer, pointer, 1);
+  }
+
+  /**
+   * Resolves once listeners of this thread were queued since the last `notifyAll`,
+   * or after `timeout` ms. Returns false on timeout
+   */
+  async waitForNotifications(timeout?: number): Promise<boolean> {
+    const counter = this.notificationCounter();
+    const result = Atomics.waitAsync(counter, 0, this.seenNotifications, timeout);
+    const value = result.async ? await result.value : result.value;
+    return value !== "timed-out";
+  }
+
   notifyAll() {
     this.withLock(() => {
+      this.seenNotifications = Atomics.load(this.notificationCounter(), 0);
       const arr = this.ops.takeNotifications();
       arr.forEach((id) => {
         const listener = this.listeners.get(id);
@@ -288,6 +836,126 @@ export class AnyStore {
     this.ops.deleteRowFromTable(tableID, rowID);
   }
 
+  /**
+   * The key of the row, null if the row does not exist
+   */
+  getRowKey(tableID: number, rowID: number): Something | null {
+    const tag = checkStatus(this.ops.exports.table_get_row_key(tableID, rowID));
+    if (tag === NOT_FOUND) {
+      return null;
+    }
+    return { tag: keyTags[tag], value: popObjectFromStack() } as Something;
+  }
+
+  getRowVersion(tableID: number, rowID: number): number {
+    return checkStatus(this.ops.exports.table_get_row_version(tableID, rowID));
+  }
+
+  /**
+   * Blocks until the row version is no longer `lastVersion` and returns the new one,
+   * or null on timeout or if the row was deleted. Throws on the main thread
+   * and inside `withLock`, where no other thread could write the row
+   */
+  waitForRowChange(
+    tableID: number,
+    rowID: number,
+    lastVersion: number,
+    timeout = -1,
+  ): number | null {
+    const status = this.ops.exports.row_wait_change(
+      tableID,
+      rowID,
+      lastVersion,
+      timeout,
+    );
+    if (status === TIMED_OUT || status === NOT_FOUND) {
+      return null;
+    }
+    return checkStatus(status);
+  }
+
+  /**
+   * Writes the value only if the row is still at the given version,
+   * returns the new version or null if someone else wrote the row first
+   */
+  insertIfVersion(
+    tableID: number,
+    col: number,
+    rowID: number,
+    value: unknown,
+    tag: Something["tag"],
+    version: number,
+  ): number | null {
+    this.ops.putSomethingOnStack(value, tag);
+    const status = this.ops.exports.table_insert_if_version(
+      tableID,
+      col,
+      rowID,
+      version,
+    );
+    if (status === VERSION_CONFLICT) {
+      return null;
+    }
+    return checkStatus(status);
+  }
+
+  /**
+   * Adds to a number column without taking the global lock,
+   * returns the value of the column after the addition
+   */
+  addOnTable(
+    tableID: number,
+    col: number,
+    rowID: number,
+    delta: number,
+    tag: Something["tag"],
+  ): number {
+    if (tag === "i32") {
+      checkStatus(this.ops.exports.table_add_i32(tableID, col, rowID, delta));
+    } else {
+      checkStatus(this.ops.exports.table_add_f64(tableID, col, rowID, delta));
+    }
+    return popObjectFromStack();
+  }
+
+  /**
+   * Writes value only if the column is equal to expected,
+   * returns true if the write happened, false when the column did not equal
+   * expected, even if it already held value
+   */
+  compareAndSwapOnTable(
+    tableID: number,
+    col: number,
+    rowID: number,
+    expected: unknown,
+    value: unknown,
+    tag: Something["tag"],
+  ): boolean {
+    this.ops.putSomethingOnStack(expected, tag);
+    this.ops.putSomethingOnStack(value, tag);
+    const swapped = checkStatus(
+      this.ops.exports.table_compare_and_swap(tableID, col, rowID),
+    );
+    popObjectFromStack();
+    return swapped === 1;
+  }
+
+  /**
+   * Appends to a string or blob column,
+   * returns the value of the column after the append
+   */
+  appendOnTable(
+    tableID: number,
+    col: number,
+    rowID: number,
+    value: string | Uint8Array,
+    tag: Something["tag"],
+  ): Something["value"] {
+    this.ops.putSomethingOnStack(value, tag);
+    checkStatus(this.ops.exports.table_append(tableID, col, rowID));
+    return popObjectFromStack();
+  }
+
   removeListenerFromRow(tableID: number, rowID: number, listenerID: number) {
     this.listeners.delete(listenerID);
     this.ops.removeListenerFromRow(tableID, rowID, listenerID);
@@ -298,6 +966,43 @@ export class AnyStore {
     return getWholeStack();
   }
 
+  /**
+   * Reads only the given columns of a row
+   */
+  getColumnsFromTable(
+    tableID: number,
+    rowID: number,
+    cols: number[],
+  ): Something["value"][] {
+    cols.forEach((col) => this.ops.somethingPushi32ToStack(col));
+    checkStatus(this.ops.exports.table_get_columns(tableID, rowID, cols.length));
+    return getWholeStack();
+  }
+
+  /**
+   * Reads the given columns of many rows at once,
+   * rows that don't exist read as nulls
+   */
+  getColumnsOfRows(
+    tableID: number,
+    rowIDs: number[],
+    cols: number[],
+  ): Something["value"][][] {
+    rowIDs.forEach((rowID) => this.ops.somethingPushi32ToStack(rowID));
+    cols.forEach((col) => this.ops.somethingPushi32ToStack(col));
+    checkStatus(
+      this.ops.exports.table_get_rows_columns(
+        tableID,
+        rowIDs.length,
+        cols.length,
+      ),
+    );
+    const values = getWholeStack();
+    return rowIDs.map((_, i) =>
+      values.slice(i * cols.length, (i + 1) * cols.length),
+    );
+  }
+
   createWorker(): WorkerData {
     this.workerID += 1;
     return {
@@ -306,6 +1011,14 @@ export class AnyStore {
     };
   }
 
+  /**
+   * Cleans up after a terminated worker: drops its listeners, pending notifications
+   * and change feeds, and releases the lock if the worker held it
+   */
+  detachWorker(workerID: number) {
+    this.ops.exports.worker_detach(workerID);
+  }
+
   static i32(value: number): I32 {
     return { tag: "i32", value };
   }
@@ -353,9 +1066,15 @@ class Ops {
     return this.out;
   }
 
-  createTable(name: string) {
+  createTable(name: string, autoKey: AutoKeyMode) {
     this.putSomethingOnStack(name, "string");
-    return this.exports.table_create();
+    if (autoKey === "none") {
+      return this.exports.table_create();
+    }
+    const mode = autoKey === "increment" ? 1 : 2;
+    // wasm has no source of randomness, random keys are seeded from here
+    const [high, low] = crypto.getRandomValues(new BigUint64Array(2));
+    return checkStatus(this.exports.table_create_with_auto_key(mode, high, low));
   }
 
   getTableIDFromName(name: string): number | null {
@@ -375,21 +1094,25 @@ class Ops {
       this.somethingPushf64ToStack(value as number);
     } else if (tag === "blob") {
       this.somethingPushBlobToStack(value as Uint8Array);
+    } else if (tag === "ref") {
+      const ref =
+        value instanceof RowRef ? value : (value as { ref(): RowRef }).ref();
+      this.exports.something_push_row_ref(ref.tableID, ref.rowID);
     } else if (tag === "null") {
       this.pushNullToStack();
     }
   }
 
   removeListenerFromRow(tableID: number, rowID: number, listenerID: number) {
-    this.exports.table_remove_listener(tableID, listenerID, rowID);
+    checkStatus(this.exports.table_remove_listener(tableID, listenerID, rowID));
   }
 
   deleteRowFromTable(tableID: number, rowID: number): void {
-    this.exports.delete_row_from_table(tableID, rowID);
+    checkStatus(this.exports.delete_row_from_table(tableID, rowID));
   }
 
   getRowFromTable(tableID: number, rowID: number): void {
-    this.exports.table_get_row(tableID, rowID);
+    checkStatus(this.exports.table_get_row(tableID, rowID));
   }
 
   pushNullToStack(): void {
@@ -419,7 +1142,7 @@ class Ops {
   }
 
   addListenerToRow(tableID: number, rowID: number): number {
-    return this.exports.table_add_listener_to_row(tableID, rowID);
+    return checkStatus(this.exports.table_add_listener_to_row(tableID, rowID));
   }
 
   somethingPushi32ToStack(value: number): void {
@@ -432,10 +1155,10 @@ class Ops {
   }
 
   tableInsert(tableID: number, col: number, rowID: number): void {
-    this.exports.table_insert(tableID, col, rowID);
+    checkStatus(this.exports.table_insert(tableID, col, rowID));
   }
 
   tableGetSomething(tableID: number, col: number, rowID: number): void {
-    this.exports.table_get_something(tableID, col, rowID);
+    checkStatus(this.exports.table_get_something(tableID, col, rowID));
   }
 }
@@ -25,14 +25,90 @@ export class _Row<T extends ColMap> {
     });
   }
 
-  addListener(fn: () => void) {
-    return this.table.addListenerToRow(this.rowID, fn);
+  /**
+   * With `colNames` the listener is only called for writes to those columns
+   */
+  addListener(fn: () => void, colNames?: (keyof T)[]) {
+    return this.table.addListenerToRow(this.rowID, fn, colNames);
   }
 
   delete() {
     return this.table.deleteRow(this.rowID);
   }
 
+  /**
+   * Reference to this row that can be stored in a `ref` column
+   */
+  ref() {
+    return this.table._ref(this.rowID);
+  }
+
+  /**
+   * Writes several columns at once, listeners are notified once
+   */
+  set(values: Partial<{ [K in keyof T]: ValueMap[T[K]] | null }>) {
+    this.table._insertMany(this.rowID, values);
+  }
+
+  /**
+   * The version is bumped on every write to the row
+   */
+  version(): number {
+    return this.table._version(this.rowID);
+  }
+
+  /**
+   * Blocks the worker until the row is written after `version`, returns the new version
+   * or null on timeout or deletion. Not available on the main thread or inside `withLock`
+   */
+  waitForChange(version: number, timeout?: number): number | null {
+    return this.table._waitForChange(this.rowID, version, timeout);
+  }
+
+  /**
+   * Writes the value only if the row was not written since `version`,
+   * returns the new version or null on conflict so the caller can retry
+   */
+  setIfVersion<K extends keyof T>(
+    colName: K,
+    value: ValueMap[T[K]] | null,
+    version: number,
+  ): number | null {
+    return this.table._insertIfVersion(this.rowID, colName, value, version);
+  }
+
+  /**
+   * Atomically adds to an i32 or f64 column, null counts as 0
+   */
+  add<K extends keyof T>(colName: K, delta: number): number {
+    return this.table._add(this.rowID, colName, delta);
+  }
+
+  /**
+   * Atomically writes value if the column is equal to expected, returns whether the write happened
+   */
+  compareAndSwap<K extends keyof T>(
+    colName: K,
+    expected: ValueMap[T[K]] | null,
+    value: ValueMap[T[K]] | null,
+  ): boolean {
+    return this.table._compareAndSwap(this.rowID, colName, expected, value);
+  }
+
+  /**
+   * Atomically appends to a string or blob column
+   */
+  append<K extends keyof T>(colName: K, value: ValueMap[T[K]]) {
+    return this.table._append(this.rowID, colName, value as string | Uint8Array);
+  }
+
+  /**
+   * Reads only the given columns
+   */
+  pick<K extends keyof T>(...colNames: K[]) {
+    return this.table.getColumns(this.rowID, colNames);
+  }
+
   removeListener(listenerID: number) {
     this.table.removeListenerFromRow(listenerID, this.rowID);
   }
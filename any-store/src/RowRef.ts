@@ -0,0 +1,29 @@
+import type { Row } from "./Row";
+import type { Table } from "./Table";
+import type { ColMap } from "./types";
+
+/**
+ * Typed reference to a row, stored in `ref` columns instead of a bare row ID
+ */
+export class RowRef {
+  /**
+   * Tables created or opened with `getTable` in this thread, used to resolve references
+   */
+  static tables: Map<number, Table<any>> = new Map();
+
+  constructor(
+    public readonly tableID: number,
+    public readonly rowID: number,
+  ) {}
+
+  /**
+   * The referenced row, null if it was deleted or its table was not opened in this thread
+   */
+  resolve<T extends ColMap = any>(): Row<T> | null {
+    const table = RowRef.tables.get(this.tableID);
+    if (!table) {
+      return null;
+    }
+    return table._rowFromID(this.rowID);
+  }
+}
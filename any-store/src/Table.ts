@@ -1,6 +1,16 @@
 import type { AnyStore } from "./AnyStore";
 import { _Row, type Row } from "./Row";
-import type { ColMap, Something, ValueMap } from "./types";
+import { RowRef } from "./RowRef";
+import type {
+  AggregateKind,
+  ColMap,
+  JoinOn,
+  OnDelete,
+  Predicate,
+  QueryOptions,
+  Something,
+  ValueMap,
+} from "./types";
 
 export class Table<T extends ColMap> {
   colMap: Map<string, number> = new Map();
@@ -52,22 +62,172 @@ export class Table<T extends ColMap> {
     this.wdb.clearTable(this.tableID);
   }
 
+  /**
+   * Declares that the column holds row IDs of the other table,
+   * `onDelete` decides what happens to this row when the referenced row is deleted
+   */
+  references(colName: keyof T, table: Table<any>, onDelete: OnDelete) {
+    this.wdb.addReference(
+      this.tableID,
+      this.colMap.get(colName as string)!,
+      table.tableID,
+      onDelete,
+    );
+  }
+
   private tagOf(colName: keyof T): Something["tag"] {
     return this.tags[colName];
   }
 
-  addListenerToRow(rowID: number, fn: () => void) {
-    return this.wdb.addListenerToRow(this.tableID, rowID, fn);
+  /**
+   * Called by `notifyAll` after rows were created, deleted or written, or the table was cleared
+   */
+  addListener(fn: () => void) {
+    return this.wdb.addTableListener(this.tableID, fn);
+  }
+
+  removeListener(listenerID: number) {
+    this.wdb.removeTableListener(this.tableID, listenerID);
+  }
+
+  /**
+   * Called by `notifyAll` when the row with the key is created, written or deleted.
+   * The row doesn't need to exist yet and the listener survives its deletion
+   */
+  addListenerToKey(key: Something, fn: () => void) {
+    return this.wdb.addKeyListener(this.tableID, key, fn);
+  }
+
+  removeListenerFromKey(key: Something, listenerID: number) {
+    this.wdb.removeKeyListener(this.tableID, key, listenerID);
+  }
+
+  addListenerToRow(rowID: number, fn: () => void, colNames?: (keyof T)[]) {
+    const cols = colNames?.map((colName) => this.colMap.get(colName as string)!);
+    return this.wdb.addListenerToRow(this.tableID, rowID, fn, cols);
   }
 
   getRowData(rowID: number): Something["value"][] {
     return this.wdb.getRowFromTable(this.tableID, rowID);
   }
 
+  /**
+   * Reads only the given columns of a row into an object
+   */
+  getColumns<K extends keyof T>(
+    rowID: number,
+    colNames: K[],
+  ): { [P in K]: ValueMap[T[P]] | null } {
+    const cols = colNames.map((colName) => this.colMap.get(colName as string)!);
+    const values = this.wdb.getColumnsFromTable(this.tableID, rowID, cols);
+    return this.toObject(colNames, values);
+  }
+
+  /**
+   * Reads the given columns of many rows, e.g. the result of `query`,
+   * under a single lock, missing rows and rows removed by a clear read as nulls
+   */
+  select<K extends keyof T>(
+    rowIDs: number[],
+    colNames: K[],
+  ): { [P in K]: ValueMap[T[P]] | null }[] {
+    const cols = colNames.map((colName) => this.colMap.get(colName as string)!);
+    return this.wdb
+      .getColumnsOfRows(this.tableID, rowIDs, cols)
+      .map((values) => this.toObject(colNames, values));
+  }
+
+  private toObject<K extends keyof T>(
+    colNames: K[],
+    values: Something["value"][],
+  ): { [P in K]: ValueMap[T[P]] | null } {
+    const object: any = {};
+    colNames.forEach((colName, i) => {
+      object[colName] = values[i];
+    });
+    return object;
+  }
+
   _insert(rowID: number, value: unknown, tag: Something["tag"], col: number) {
     this.wdb.insertOnTable(this.tableID, col, rowID, value, tag);
   }
 
+  _insertMany(rowID: number, values: Partial<{ [K in keyof T]: unknown }>) {
+    this.wdb.insertManyOnTable(this.tableID, rowID, this.columnValues(values));
+  }
+
+  private columnValues(values: Partial<{ [K in keyof T]: unknown }>) {
+    return Object.entries(values).map(([colName, value]) => ({
+      col: this.colMap.get(colName)!,
+      value,
+      tag: this.tagOf(colName as keyof T),
+    }));
+  }
+
+  _version(rowID: number): number {
+    return this.wdb.getRowVersion(this.tableID, rowID);
+  }
+
+  _waitForChange(rowID: number, lastVersion: number, timeout?: number) {
+    return this.wdb.waitForRowChange(this.tableID, rowID, lastVersion, timeout);
+  }
+
+  _insertIfVersion(
+    rowID: number,
+    colName: keyof T,
+    value: unknown,
+    version: number,
+  ): number | null {
+    const col = this.colMap.get(colName as string)!;
+    return this.wdb.insertIfVersion(
+      this.tableID,
+      col,
+      rowID,
+      value,
+      this.tagOf(colName),
+      version,
+    );
+  }
+
+  _add(rowID: number, colName: keyof T, delta: number): number {
+    const col = this.colMap.get(colName as string)!;
+    return this.wdb.addOnTable(
+      this.tableID,
+      col,
+      rowID,
+      delta,
+      this.tagOf(colName),
+    );
+  }
+
+  _compareAndSwap(
+    rowID: number,
+    colName: keyof T,
+    expected: unknown,
+    value: unknown,
+  ): boolean {
+    const col = this.colMap.get(colName as string)!;
+    return this.wdb.compareAndSwapOnTable(
+      this.tableID,
+      col,
+      rowID,
+      expected,
+      value,
+      this.tagOf(colName),
+    );
+  }
+
+  _append(rowID: number, colName: keyof T, value: string | Uint8Array) {
+    const col = this.colMap.get(colName as string)!;
+    return this.wdb.appendOnTable(
+      this.tableID,
+      col,
+      rowID,
+      value,
+      this.tagOf(colName),
+    );
+  }
+
   removeListenerFromRow(listenerID: number, rowID: number) {
     this.wdb.removeListenerFromRow(this.tableID, rowID, listenerID);
   }
@@ -86,6 +246,27 @@ export class Table<T extends ColMap> {
     return new this.rowConstructor<T>(this, id, key) as Row<T>;
   }
 
+  /**
+   * Creates a row with a key generated by the table,
+   * the table must have been created with an autoKey mode
+   */
+  createAutoRow() {
+    const { rowID, key } = this.wdb.createAutoRow(this.tableID);
+    return new this.rowConstructor<T>(this, rowID, key) as Row<T>;
+  }
+
+  _rowFromID(rowID: number): Row<T> | null {
+    const key = this.wdb.getRowKey(this.tableID, rowID);
+    if (key === null) {
+      return null;
+    }
+    return new this.rowConstructor<T>(this, rowID, key) as Row<T>;
+  }
+
+  _ref(rowID: number): RowRef {
+    return new RowRef(this.tableID, rowID);
+  }
+
   getRow(key: Something) {
     const rowID = this.wdb.getRowID(this.tableID, key);
     if (rowID === null) {
@@ -94,6 +275,173 @@ export class Table<T extends ColMap> {
     return new this.rowConstructor<T>(this, rowID, key) as Row<T>;
   }
 
+  /**
+   * Computes count, sum, min, max or avg of a column,
+   * pass `where` to aggregate only the rows where that column is equal to the value
+   */
+  aggregate<K extends keyof T, W extends keyof T>(
+    kind: AggregateKind,
+    colName: K,
+    where?: [W, ValueMap[T[W]]],
+  ): Something["value"] | null {
+    const col = this.colMap.get(colName as string)!;
+    const filter = where && {
+      col: this.colMap.get(where[0] as string)!,
+      value: where[1],
+      tag: this.tagOf(where[0]),
+    };
+    return this.wdb.aggregate(this.tableID, col, kind, filter);
+  }
+
+  /**
+   * Buckets the rows by the value of groupColName and aggregates colName in every bucket,
+   * groups are sorted by their value and rows with null form a group too
+   */
+  groupBy<G extends keyof T, A extends AggregateKind>(
+    groupColName: G,
+    colName: keyof T,
+    kinds: A[],
+  ): ({ group: ValueMap[T[G]] | null } & Record<A, Something["value"] | null>)[] {
+    return this.wdb.groupBy(
+      this.tableID,
+      this.colMap.get(groupColName as string)!,
+      this.colMap.get(colName as string)!,
+      kinds,
+    ) as any;
+  }
+
+  /**
+   * Returns the ids of the rows matching the predicate sorted by options.orderBy or by row id,
+   * indexed columns are used to avoid scanning the whole table
+   */
+  query(predicate: Predicate<T> | null, options?: QueryOptions<T>): number[] {
+    return this.wdb.query(
+      this.tableID,
+      predicate as Predicate | null,
+      (col) => [this.colMap.get(col)!, this.tagOf(col)],
+      options as QueryOptions,
+    );
+  }
+
+  /**
+   * Deletes the rows matching the predicate, returns how many were deleted
+   */
+  deleteWhere(predicate: Predicate<T> | null): number {
+    return this.wdb.deleteWhere(
+      this.tableID,
+      predicate as Predicate | null,
+      (col) => [this.colMap.get(col)!, this.tagOf(col)],
+    );
+  }
+
+  /**
+   * Writes the values to the rows matching the predicate, returns how many were updated
+   */
+  updateWhere(
+    predicate: Predicate<T> | null,
+    values: Partial<{ [K in keyof T]: ValueMap[T[K]] | null }>,
+  ): number {
+    return this.wdb.updateWhere(
+      this.tableID,
+      predicate as Predicate | null,
+      (col) => [this.colMap.get(col)!, this.tagOf(col)],
+      this.columnValues(values),
+    );
+  }
+
+  /**
+   * Joins the column with the other table in a single call,
+   * returns the matching `[rowID, otherRowID]` pairs sorted by row ID
+   */
+  join<U extends ColMap>(
+    colName: keyof T,
+    other: Table<U>,
+    on: JoinOn<U>,
+  ): [number, number][] {
+    return this.wdb.join(
+      this.tableID,
+      this.colMap.get(colName as string)!,
+      other.tableID,
+      on,
+      (col) => [other.colMap.get(col)!, other.tagOf(col)],
+    ) as [number, number][];
+  }
+
+  /**
+   * Like `join` but reads the given columns of both rows of every match
+   */
+  joinColumns<U extends ColMap, K extends keyof T, L extends keyof U>(
+    colName: keyof T,
+    other: Table<U>,
+    on: JoinOn<U>,
+    leftCols: K[],
+    rightCols: L[],
+  ): {
+    left: { [P in K]: ValueMap[T[P]] | null };
+    right: { [P in L]: ValueMap[U[P]] | null };
+  }[] {
+    const rows = this.wdb.join(
+      this.tableID,
+      this.colMap.get(colName as string)!,
+      other.tableID,
+      on,
+      (col) => [other.colMap.get(col)!, other.tagOf(col)],
+      {
+        left: leftCols.map((col) => this.colMap.get(col as string)!),
+        right: rightCols.map((col) => other.colMap.get(col as string)!),
+      },
+    );
+    return rows.map((values) => ({
+      left: this.toObject(leftCols, values),
+      right: other.toObject(rightCols, values.slice(leftCols.length)),
+    }));
+  }
+
+  /**
+   * Live query, returns the rows matching now and the listener ID.
+   * `fn` is called by `notifyAll` with the rows that entered and left the result,
+   * only the rows that changed are re-evaluated
+   */
+  subscribe(
+    predicate: Predicate<T> | null,
+    fn: (changes: { added: number[]; removed: number[] }) => void,
+  ): { listenerID: number; rows: number[] } {
+    return this.wdb.subscribe(
+      this.tableID,
+      predicate as Predicate | null,
+      (col) => [this.colMap.get(col)!, this.tagOf(col)],
+      fn,
+    );
+  }
+
+  unsubscribe(listenerID: number) {
+    this.wdb.unsubscribe(this.tableID, listenerID);
+  }
+
+  /**
+   * Changes to this table show up in `AnyStore.takeChanges` of this worker,
+   * with the old and new values when `withValues` is set
+   */
+  enableChangeFeed(withValues = false) {
+    this.wdb.enableChangeFeed(this.tableID, withValues);
+  }
+
+  disableChangeFeed() {
+    this.wdb.disableChangeFeed(this.tableID);
+  }
+
+  createIndex(colName: keyof T) {
+    this.wdb.createIndex(this.tableID, this.colMap.get(colName as string)!);
+  }
+
+  /**
+   * Makes the column unique, writing a value already held by another row throws.
+   * Throws if the column already holds duplicates
+   */
+  unique(colName: keyof T) {
+    this.wdb.addUnique(this.tableID, this.colMap.get(colName as string)!);
+  }
+
   where<K extends keyof T>(colName: K, value: ValueMap[T[K]]): number[] {
     return this.wdb.withColsEqual(
       this.tableID,
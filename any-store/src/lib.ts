@@ -1,4 +1,5 @@
 export * from "./AnyStore";
 export * from "./Table";
 export * from "./Row";
+export * from "./RowRef";
 export * from "./types";
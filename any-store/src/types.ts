@@ -1,3 +1,5 @@
+import type { RowRef } from "./RowRef";
+
 export type ColMap = Record<string, Something["tag"]>;
 
 export type ValueMap = {
@@ -6,6 +8,7 @@ export type ValueMap = {
   null: null;
   f64: number;
   blob: Uint8Array;
+  ref: RowRef;
 };
 
 export type I32 = { tag: "i32"; value: number };
@@ -13,5 +16,74 @@ export type String = { tag: "string"; value: string };
 export type Null = { tag: "null"; value: null };
 export type F64 = { tag: "f64"; value: number };
 export type Blob = { tag: "blob"; value: Uint8Array };
+export type Ref = { tag: "ref"; value: RowRef };
+
+export type Something = I32 | String | Null | F64 | Blob | Ref;
+
+export type AutoKeyMode = "none" | "increment" | "random";
+
+export type TableOptions = {
+  autoKey?: AutoKeyMode;
+};
+
+/**
+ * What happened to a row, "overflowed" means changes were dropped and the
+ * table should be read again
+ */
+export type ChangeKind =
+  | "created"
+  | "written"
+  | "deleted"
+  | "cleared"
+  | "overflowed";
+
+/**
+ * A change read from a change feed, `old` and `new` hold the values of `cols`
+ * when the feed was enabled with values
+ */
+export type Change = {
+  table: number;
+  row: number;
+  kind: ChangeKind;
+  cols: number[];
+  old?: Something[];
+  new?: Something[];
+};
+
+/**
+ * What happens to the referencing rows when the referenced row is deleted
+ */
+export type OnDelete = "cascade" | "restrict" | "setNull";
+
+export type AggregateKind = "count" | "sum" | "min" | "max" | "avg";
+
+export type Comparison = "==" | "!=" | "<" | "<=" | ">" | ">=";
 
-export type Something = I32 | String | Null | F64 | Blob;
+/**
+ * Condition over the columns of a table, evaluated inside the store
+ */
+export type Predicate<T extends ColMap = ColMap> =
+  | {
+      [K in keyof T]: { col: K; op: Comparison; value: ValueMap[T[K]] | null };
+    }[keyof T]
+  | { [K in keyof T]: { col: K; in: ValueMap[T[K]][] } }[keyof T]
+  | { col: keyof T; isNull: true }
+  | { [K in keyof T]: { col: K; prefix: ValueMap[T[K]] } }[keyof T]
+  | { and: Predicate<T>[] }
+  | { or: Predicate<T>[] }
+  | { not: Predicate<T> };
+
+/**
+ * What a join matches the left column against: a column of the right table,
+ * the right row IDs or the right row keys
+ */
+export type JoinOn<U extends ColMap = ColMap> = { col: keyof U } | "rowID" | "key";
+
+export type QueryOptions<T extends ColMap = ColMap> = {
+  /**
+   * Columns to sort by, the first one is the primary order. Rows are sorted by id otherwise
+   */
+  orderBy?: { col: keyof T; desc?: boolean }[];
+  limit?: number;
+  offset?: number;
+};
@@ -0,0 +1,77 @@
+use crate::value::Something;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregate {
+    /// Number of non null values
+    Count,
+    Sum,
+    Min,
+    Max,
+    Avg,
+}
+
+impl Aggregate {
+    pub fn from_u8(kind: u8) -> Option<Self> {
+        match kind {
+            0 => Some(Aggregate::Count),
+            1 => Some(Aggregate::Sum),
+            2 => Some(Aggregate::Min),
+            3 => Some(Aggregate::Max),
+            4 => Some(Aggregate::Avg),
+            _ => None,
+        }
+    }
+}
+
+/// Computes every aggregate of a column in a single pass.
+/// Nulls are skipped, sum and avg only consider ints and floats.
+#[derive(Debug, Clone, Default)]
+pub struct Accumulator<'a> {
+    count: u32,
+    numeric_count: u32,
+    sum: f64,
+    min: Option<&'a Something>,
+    max: Option<&'a Something>,
+}
+
+impl<'a> Accumulator<'a> {
+    pub fn new() -> Self {
+        return Accumulator::default();
+    }
+
+    pub fn add(&mut self, value: &'a Something) {
+        match value {
+            Something::Null => return,
+            Something::Int(v) => {
+                self.sum += *v as f64;
+                self.numeric_count += 1;
+            }
+            Something::Float(v) => {
+                self.sum += v;
+                self.numeric_count += 1;
+            }
+            _ => {}
+        }
+        self.count += 1;
+        if self.min.is_none_or(|min| value < min) {
+            self.min = Some(value);
+        }
+        if self.max.is_none_or(|max| value > max) {
+            self.max = Some(value);
+        }
+    }
+
+    /// Like in SQL, aggregates other than count are null when there are no values
+    pub fn result(&self, aggregate: Aggregate) -> Something {
+        match aggregate {
+            Aggregate::Count => Something::Int(self.count as i32),
+            Aggregate::Sum if self.numeric_count > 0 => Something::Float(self.sum),
+            Aggregate::Avg if self.numeric_count > 0 => {
+                Something::Float(self.sum / self.numeric_count as f64)
+            }
+            Aggregate::Min => self.min.cloned().unwrap_or_default(),
+            Aggregate::Max => self.max.cloned().unwrap_or_default(),
+            _ => Something::Null,
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use crate::value::Something;
+
+/// Changes a feed keeps until its worker takes them, past that they are dropped
+/// and the worker gets a single `Overflowed` change
+pub const MAX_PENDING_CHANGES: usize = 65_536;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Created = 0,
+    Written = 1,
+    Deleted = 2,
+    /// The whole table was cleared, the row id is not meaningful
+    Cleared = 3,
+    /// The worker didn't take the changes in time and they were dropped,
+    /// the table has to be read again. The row id is not meaningful
+    Overflowed = 4,
+}
+
+/// A change to a row. `old` and `new` hold the values of `cols`
+/// and are only filled for feeds that asked for values
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub row_id: u32,
+    pub cols: Vec<usize>,
+    pub old: Vec<Something>,
+    pub new: Vec<Something>,
+}
+
+impl Change {
+    pub fn new(kind: ChangeKind, row_id: u32) -> Self {
+        return Change {
+            kind,
+            row_id,
+            cols: Vec::new(),
+            old: Vec::new(),
+            new: Vec::new(),
+        };
+    }
+
+    /// Appends the change to the buffer sent to js, little endian:
+    /// `table u32, row u32, kind u8, n_cols u32, cols u32 * n_cols, has_values u8`
+    /// followed by the old and new value of every column when `has_values` is 1
+    pub fn encode(&self, table_id: usize, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&(table_id as u32).to_le_bytes());
+        buf.extend_from_slice(&self.row_id.to_le_bytes());
+        buf.push(self.kind as u8);
+        buf.extend_from_slice(&(self.cols.len() as u32).to_le_bytes());
+        for col in &self.cols {
+            buf.extend_from_slice(&(*col as u32).to_le_bytes());
+        }
+        let has_values = !self.cols.is_empty() && self.old.len() == self.cols.len();
+        buf.push(has_values as u8);
+        if has_values {
+            for (old, new) in self.old.iter().zip(&self.new) {
+                old.encode(buf);
+                new.encode(buf);
+            }
+        }
+    }
+}
+
+/// Changes of a table waiting to be taken by a worker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangeFeed {
+    pub worker: usize,
+    pub with_values: bool,
+    pub changes: Vec<Change>,
+}
+
+impl ChangeFeed {
+    pub fn new(worker: usize, with_values: bool) -> Self {
+        return ChangeFeed {
+            worker,
+            with_values,
+            changes: Vec::new(),
+        };
+    }
+
+    /// Once `MAX_PENDING_CHANGES` are pending they are replaced by a single
+    /// `Overflowed` change and further changes are dropped until the feed is taken
+    pub fn push(&mut self, change: &Change) {
+        if self.is_overflowed() {
+            return;
+        }
+        if self.changes.len() >= MAX_PENDING_CHANGES {
+            self.changes = vec![Change::new(ChangeKind::Overflowed, 0)];
+            return;
+        }
+        if self.with_values {
+            self.changes.push(change.clone());
+        } else {
+            self.changes.push(Change {
+                cols: change.cols.clone(),
+                ..Change::new(change.kind, change.row_id)
+            });
+        }
+    }
+
+    fn is_overflowed(&self) -> bool {
+        return self
+            .changes
+            .first()
+            .is_some_and(|change| change.kind == ChangeKind::Overflowed);
+    }
+}
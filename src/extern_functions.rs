@@ -6,6 +6,7 @@ pub enum MockValue {
     Float(f64),
     String(Vec<u8>),
     Blob(Vec<u8>),
+    RowRef { table: u32, row: u32 },
     Null,
 }
 
@@ -34,6 +35,8 @@ mod extern_functions_mod {
         #[wasm_bindgen]
         fn js_put_f64(value: f64);
         #[wasm_bindgen]
+        fn js_put_row_ref(table: u32, row: u32);
+        #[wasm_bindgen]
         fn js_log_stack_value();
         #[wasm_bindgen]
         fn js_push_null();
@@ -47,6 +50,12 @@ mod extern_functions_mod {
         fn js_read_blob_byte(index: usize) -> u8;
         #[wasm_bindgen]
         fn unsafe_worker_id() -> i32;
+        #[wasm_bindgen]
+        fn js_performance_now() -> f64;
+    }
+
+    pub fn now_ms() -> f64 {
+        return js_performance_now();
     }
 
     pub fn is_main_thread() -> bool {
@@ -83,6 +92,10 @@ mod extern_functions_mod {
         js_put_f64(value);
     }
 
+    pub fn safe_put_row_ref(table: u32, row: u32) {
+        js_put_row_ref(table, row);
+    }
+
     pub fn safe_js_pop_stack() {
         js_pop_stack();
     }
@@ -174,6 +187,10 @@ mod extern_functions_mod {
         MOCK_STACK.with(|stack| stack.borrow_mut().push(MockValue::Float(value)));
     }
 
+    pub fn safe_put_row_ref(table: u32, row: u32) {
+        MOCK_STACK.with(|stack| stack.borrow_mut().push(MockValue::RowRef { table, row }));
+    }
+
     pub fn safe_log_stack_value() {
         MOCK_STACK.with(|stack| {
             if let Some(val) = stack.borrow().last() {
@@ -215,6 +232,13 @@ mod extern_functions_mod {
         MOCK_LOGS.with(|logs| logs.borrow_mut().push(s.to_string()));
     }
 
+    pub fn now_ms() -> f64 {
+        let since_epoch = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        return since_epoch.as_secs_f64() * 1000.0;
+    }
+
     pub fn is_main_thread() -> bool {
         worker_id() == 0
     }
@@ -0,0 +1,85 @@
+use crate::{query::Comparison, value::Something};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::Bound,
+};
+
+/// Secondary index from the values of a column to the rows holding them.
+/// Nulls are not indexed.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ColumnIndex {
+    entries: BTreeMap<Something, BTreeSet<u32>>,
+}
+
+impl ColumnIndex {
+    pub fn new() -> Self {
+        return ColumnIndex::default();
+    }
+
+    pub fn insert(&mut self, value: &Something, row_id: u32) {
+        if value == &Something::Null {
+            return;
+        }
+        self.entries
+            .entry(value.clone())
+            .or_default()
+            .insert(row_id);
+    }
+
+    pub fn remove(&mut self, value: &Something, row_id: u32) {
+        let Some(rows) = self.entries.get_mut(value) else {
+            return;
+        };
+        rows.remove(&row_id);
+        if rows.is_empty() {
+            self.entries.remove(value);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn get(&self, value: &Something) -> impl Iterator<Item = u32> + '_ {
+        return self.entries.get(value).into_iter().flatten().copied();
+    }
+
+    pub fn has_duplicates(&self) -> bool {
+        return self.entries.values().any(|rows| rows.len() > 1);
+    }
+
+    /// Rows that may satisfy the comparison, `Ne` can't be answered by the index
+    pub fn compare(&self, op: Comparison, value: &Something) -> Option<Vec<u32>> {
+        let bounds = match op {
+            Comparison::Eq => return Some(self.get(value).collect()),
+            Comparison::Ne => return None,
+            Comparison::Lt => (Bound::Unbounded, Bound::Excluded(value)),
+            Comparison::Le => (Bound::Unbounded, Bound::Included(value)),
+            Comparison::Gt => (Bound::Excluded(value), Bound::Unbounded),
+            Comparison::Ge => (Bound::Included(value), Bound::Unbounded),
+        };
+        let rows = self
+            .entries
+            .range::<Something, _>(bounds)
+            .flat_map(|(_, rows)| rows.iter().copied());
+        return Some(rows.collect());
+    }
+
+    /// Rows holding strings or blobs that start with the prefix
+    pub fn prefix(&self, prefix: &Something) -> Vec<u32> {
+        let bytes = match prefix {
+            Something::String(b) | Something::Blob(b) => b,
+            _ => return Vec::new(),
+        };
+        return self
+            .entries
+            .range(prefix..)
+            .take_while(|(value, _)| match (value, prefix) {
+                (Something::String(v), Something::String(_)) => v.starts_with(bytes),
+                (Something::Blob(v), Something::Blob(_)) => v.starts_with(bytes),
+                _ => false,
+            })
+            .flat_map(|(_, rows)| rows.iter().copied())
+            .collect();
+    }
+}
@@ -1,14 +1,21 @@
 use wasm_bindgen::prelude::wasm_bindgen;
 
 use crate::{
+    aggregate::Aggregate,
     extern_functions::*,
-    my_rwlock::MyRwLock,
-    storage::{Database, ListenerID, Operation},
+    my_rwlock::{self, MyRwLock},
+    query::{Comparison, JoinOn, OrderBy, Predicate, Query},
+    storage::{
+        AutoKey, ColumnMask, Database, ListenerID, OnDelete, Operation, Reference, StoreError,
+    },
     value::Something,
 };
 use std::{
-    cell::{RefCell, UnsafeCell},
-    sync::LazyLock,
+    cell::{Cell, RefCell, UnsafeCell},
+    sync::{
+        LazyLock,
+        atomic::{AtomicI32, Ordering},
+    },
 };
 
 struct SomethingStack {
@@ -27,12 +34,21 @@ impl SomethingStack {
     fn pop(&mut self) -> Option<Something> {
         self.stack.pop()
     }
+
+    /// Takes the last `count` values in pushed order, or none if there are fewer
+    fn pop_many(&mut self, count: usize) -> Option<Vec<Something>> {
+        let start = self.stack.len().checked_sub(count)?;
+        return Some(self.stack.split_off(start));
+    }
 }
 
 thread_local! {
     static SOMETHING_STACK: RefCell<SomethingStack> = RefCell::new(SomethingStack::new());
     static IS_BATCHING: UnsafeCell<bool> = UnsafeCell::new(false);
     static BATCHED_OPERATIONS: RefCell<Vec<Operation>> = RefCell::new(Vec::new());
+    static PREDICATE_STACK: RefCell<Vec<Predicate>> = RefCell::new(Vec::new());
+    static PREDICATE_INVALID: Cell<bool> = const { Cell::new(false) };
+    static QUERY_ORDER: RefCell<Vec<OrderBy>> = RefCell::new(Vec::new());
 }
 
 fn is_batching() -> bool {
@@ -51,6 +67,79 @@ fn pop_from_something_stack() -> Option<Something> {
     return pop_something();
 }
 
+/// Pops `count` ints keeping the order in which they were pushed
+fn pop_indices(count: usize) -> Vec<usize> {
+    let mut indices: Vec<usize> = (0..count)
+        .filter_map(|_| match pop_from_something_stack() {
+            Some(Something::Int(i)) => Some(i as usize),
+            _ => None,
+        })
+        .collect();
+    indices.reverse();
+    return indices;
+}
+
+/// Pops `count` `(col, value)` pairs, keeping the pushed order so later writes win.
+/// Nothing is popped if the stack holds fewer values
+fn pop_column_pairs(count: usize) -> Result<Vec<(usize, Something)>, StoreError> {
+    let values = SOMETHING_STACK
+        .with_borrow_mut(|stack| stack.pop_many(count * 2))
+        .ok_or(StoreError::NotFound)?;
+    let mut pairs = Vec::with_capacity(count);
+    let mut values = values.into_iter();
+    while let (Some(col), Some(value)) = (values.next(), values.next()) {
+        let Something::Int(col) = col else {
+            return Err(StoreError::TypeMismatch);
+        };
+        pairs.push((col as usize, value));
+    }
+    return Ok(pairs);
+}
+
+/// Status code returned to js, 0 when ok or the negative code of the error
+fn status<T>(result: Result<T, StoreError>) -> i32 {
+    return match result {
+        Ok(_) => 0,
+        Err(err) => err.code(),
+    };
+}
+
+fn push_predicate(predicate: Predicate) {
+    PREDICATE_STACK.with_borrow_mut(|stack| stack.push(predicate));
+}
+
+fn pop_predicate() -> Option<Predicate> {
+    return PREDICATE_STACK.with_borrow_mut(|stack| stack.pop());
+}
+
+/// Pops `count` predicates keeping the order in which they were pushed
+fn pop_predicates(count: usize) -> Vec<Predicate> {
+    let predicates = PREDICATE_STACK.with_borrow_mut(|stack| {
+        let start = stack.len().saturating_sub(count);
+        return stack.split_off(start);
+    });
+    if predicates.len() < count {
+        invalidate_predicate();
+    }
+    return predicates;
+}
+
+/// Called when a predicate is built from a bad argument or a missing operand,
+/// the placeholder pushed instead must never be run
+fn invalidate_predicate() {
+    PREDICATE_INVALID.set(true);
+}
+
+/// Pops the predicate of a query, or matches every row if there is none.
+/// Fails if any part of it was invalid, the whole predicate stack is dropped then
+fn take_predicate() -> Result<Predicate, StoreError> {
+    if PREDICATE_INVALID.replace(false) {
+        PREDICATE_STACK.take();
+        return Err(StoreError::InvalidArgument);
+    }
+    return Ok(pop_predicate().unwrap_or_else(Predicate::all));
+}
+
 struct GlobalState {
     db: MyRwLock<Database>,
 }
@@ -74,13 +163,19 @@ impl GlobalState {
         return self.db.lock.try_global_lock_write();
     }
 
+    fn release_lock_of(&self, worker_id: usize) -> bool {
+        return self.db.lock.release_global_of(worker_id);
+    }
+
     fn lock_pointer(&self) -> *const i32 {
         return self.db.lock.pointer();
     }
 
     fn with_db_mut<R, F: FnOnce(&mut Database) -> R>(&self, f: F) -> R {
         let mut state = self.db.write();
-        return f(&mut state);
+        let result = f(&mut state);
+        state.wake_workers();
+        return result;
     }
 
     fn with_db<R, F: FnOnce(&Database) -> R>(&self, f: F) -> R {
@@ -111,6 +206,21 @@ pub fn lock_pointer() -> *const i32 {
     return GLOBALS.lock_pointer();
 }
 
+/// Cleans up after a terminated worker: its listeners, queued notifications
+/// and change feeds are dropped and the global lock is released if it held it
+#[wasm_bindgen]
+pub fn worker_detach(worker_id: usize) {
+    GLOBALS.release_lock_of(worker_id);
+    GLOBALS.with_db_mut(|db| db.detach_worker(worker_id));
+}
+
+/// Counter bumped whenever a listener of the calling worker is queued,
+/// wait on it and call `db_take_notifications` when it changes
+#[wasm_bindgen]
+pub fn notification_pointer() -> *const i32 {
+    return GLOBALS.with_db_mut(|db| db.wakeup_counter(worker_id()).as_ptr());
+}
+
 #[wasm_bindgen]
 pub fn start() {
     if worker_id() == 0 {
@@ -130,6 +240,29 @@ pub fn table_create() -> usize {
     });
 }
 
+/// The seed of random keys comes from js, `crypto.getRandomValues`.
+/// Returns the table id, or the `InvalidArgument` code for an unknown mode
+#[wasm_bindgen]
+pub fn table_create_with_auto_key(mode: u8, seed_high: u64, seed_low: u64) -> i32 {
+    let name = pop_from_something_stack().expect("there should be a name for the table");
+    let Some(auto_key) = AutoKey::from_u8(mode) else {
+        return StoreError::InvalidArgument.code();
+    };
+    return GLOBALS.with_db_mut(|db| {
+        return db.create_table_with_auto_key(name, auto_key, [seed_high, seed_low]) as i32;
+    });
+}
+
+/// Creates a row with a generated key, pushes the key to the js stack and returns the row id
+#[wasm_bindgen]
+pub fn table_create_auto_row(table: usize) -> i32 {
+    let Some((key, row_id)) = GLOBALS.with_db_mut(|db| db.create_auto_row(table)) else {
+        return -1;
+    };
+    push_to_js_stack(&key);
+    return row_id as i32;
+}
+
 #[wasm_bindgen]
 pub fn table_get_row_id(table_id: usize) -> i32 {
     return GLOBALS
@@ -141,10 +274,29 @@ pub fn table_get_row_id(table_id: usize) -> i32 {
         .unwrap_or(-1);
 }
 #[wasm_bindgen]
-pub fn table_clear(table_id: usize) {
-    GLOBALS.with_db_mut(|db| {
-        db.clear_table(table_id);
-    });
+pub fn table_clear(table_id: usize) -> i32 {
+    return status(GLOBALS.with_db_mut(|db| db.clear_table(table_id)));
+}
+
+/// Declares that `from_col` of `from_table` holds row ids of `to_table`,
+/// `on_delete` is 0 for cascade, 1 for restrict and 2 for set null
+#[wasm_bindgen]
+pub fn table_add_reference(
+    from_table: usize,
+    from_col: usize,
+    to_table: usize,
+    on_delete: u8,
+) -> i32 {
+    let Some(on_delete) = OnDelete::from_u8(on_delete) else {
+        return StoreError::InvalidArgument.code();
+    };
+    let reference = Reference {
+        from_table,
+        from_col,
+        to_table,
+        on_delete,
+    };
+    return status(GLOBALS.with_db_mut(|db| db.add_reference(reference)));
 }
 
 #[wasm_bindgen]
@@ -159,37 +311,81 @@ pub fn table_get_id_from_name() -> i32 {
 }
 
 #[wasm_bindgen]
-pub fn table_get_something(table: usize, col: usize, row_id: u32) {
-    _table_get_something(table, col, row_id);
+pub fn table_get_something(table: usize, col: usize, row_id: u32) -> i32 {
+    return status(_table_get_something(table, col, row_id));
 }
 
-fn _table_get_something(table: usize, col: usize, row_id: u32) -> Option<()> {
+fn _table_get_something(table: usize, col: usize, row_id: u32) -> Result<(), StoreError> {
     return GLOBALS.with_db(|db| {
         let value = db.get_row_value(table, row_id, col)?;
         push_to_js_stack(&value);
-        return Some(());
+        return Ok(());
     });
 }
 
 #[wasm_bindgen]
-pub fn table_get_row(table: usize, row_id: u32) {
-    _table_get_row(table, row_id);
+pub fn table_get_row(table: usize, row_id: u32) -> i32 {
+    return status(_table_get_row(table, row_id));
 }
 
-fn _table_get_row(table: usize, row_id: u32) -> Option<()> {
+fn _table_get_row(table: usize, row_id: u32) -> Result<(), StoreError> {
     return GLOBALS.with_db(|db| {
         let values = db.get_row_values(table, row_id)?;
         for item in &values {
             push_to_js_stack(item);
         }
-        return Some(());
+        return Ok(());
     });
 }
 
+/// Pops `n_cols` column indices and pushes only those columns of the row
 #[wasm_bindgen]
-pub fn table_insert(table: usize, col: usize, row_id: u32) {
+pub fn table_get_columns(table: usize, row_id: u32, n_cols: usize) -> i32 {
+    let cols = pop_indices(n_cols);
+    let result = GLOBALS.with_db(|db| db.get_row_columns(table, row_id, &cols));
+    return match result {
+        Ok(values) => {
+            values.iter().for_each(push_to_js_stack);
+            0
+        }
+        Err(err) => err.code(),
+    };
+}
+
+/// Pops `n_cols` column indices and then `n_rows` row ids, pushes the columns of each row.
+/// Rows that don't exist, or were removed by a clear, push nulls so every row pushes `n_cols` values
+#[wasm_bindgen]
+pub fn table_get_rows_columns(table: usize, n_rows: usize, n_cols: usize) -> i32 {
+    let cols = pop_indices(n_cols);
+    let rows = pop_indices(n_rows);
+    let result = GLOBALS.with_db(|db| {
+        let mut values = Vec::with_capacity(rows.len() * cols.len());
+        for row_id in rows {
+            let row = db.get_row_columns(table, row_id as u32, &cols);
+            match row.map_err(StoreError::stale_as_not_found) {
+                Ok(row) => values.extend(row),
+                Err(StoreError::NotFound) => {
+                    values.resize(values.len() + cols.len(), Something::Null)
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        return Ok(values);
+    });
+    return match result {
+        Ok(values) => {
+            values.iter().for_each(push_to_js_stack);
+            0
+        }
+        Err(err) => err.code(),
+    };
+}
+
+/// Batched inserts are only applied on `end_batch`, so they always return 0
+#[wasm_bindgen]
+pub fn table_insert(table: usize, col: usize, row_id: u32) -> i32 {
     let Some(value) = pop_from_something_stack() else {
-        return;
+        return 0;
     };
     let op = Operation::Insert {
         table_id: table,
@@ -199,13 +395,182 @@ pub fn table_insert(table: usize, col: usize, row_id: u32) {
     };
     if is_batching() {
         BATCHED_OPERATIONS.with_borrow_mut(|ops| ops.push(op));
-    } else {
-        GLOBALS.with_db_mut(|db| {
-            db.operation(op);
+        return 0;
+    }
+    return status(GLOBALS.with_db_mut(|db| db.operation(op)));
+}
+
+/// Pops `n_cols` `(col, value)` pairs and writes them all under one lock,
+/// the row listeners are notified once
+#[wasm_bindgen]
+pub fn table_insert_many(table: usize, row_id: u32, n_cols: usize) -> i32 {
+    let values = match pop_column_pairs(n_cols) {
+        Ok(values) => values,
+        Err(err) => return err.code(),
+    };
+    let op = Operation::InsertMany {
+        table_id: table,
+        row_id,
+        values,
+    };
+    if is_batching() {
+        BATCHED_OPERATIONS.with_borrow_mut(|ops| ops.push(op));
+        return 0;
+    }
+    return status(GLOBALS.with_db_mut(|db| db.operation(op)));
+}
+
+#[wasm_bindgen]
+pub fn table_get_row_version(table: usize, row_id: u32) -> i32 {
+    return match GLOBALS.with_db(|db| db.get_row_version(table, row_id)) {
+        Ok(version) => version as i32,
+        Err(err) => err.code(),
+    };
+}
+
+/// Blocks the worker until the version of the row is no longer `last_version` and returns
+/// the new version. A negative timeout waits forever. Returns the `WouldBlock` code on the
+/// main thread and while the worker holds the global lock, since no one else could write
+/// the row. Returns `TimedOut` when the timeout expires and `NotFound` if the row is deleted.
+/// Waiters share one counter for the whole database, so any write wakes every waiter and
+/// each one takes a read lock to check its row again
+#[wasm_bindgen]
+pub fn row_wait_change(table: usize, row_id: u32, last_version: u32, timeout_ms: f64) -> i32 {
+    if is_main_thread() || my_rwlock::has_global_lock() {
+        return StoreError::WouldBlock.code();
+    }
+    let deadline = now_ms() + timeout_ms;
+    loop {
+        let checked = GLOBALS.with_db(|db| {
+            let version = db.get_row_version(table, row_id)?;
+            let changes = db.row_change_counter();
+            return Ok::<_, StoreError>((
+                version,
+                changes.load(Ordering::SeqCst),
+                changes.as_ptr(),
+            ));
         });
+        let (version, seen, counter) = match checked {
+            Ok(checked) => checked,
+            Err(err) => return err.code(),
+        };
+        if version != last_version {
+            return version as i32;
+        }
+        let remaining = if timeout_ms < 0.0 {
+            f64::INFINITY
+        } else {
+            deadline - now_ms()
+        };
+        if remaining <= 0.0 {
+            return StoreError::TimedOut.code();
+        }
+        // SAFETY: the database lives in a static, so the counter outlives the lock guard
+        let counter = unsafe { AtomicI32::from_ptr(counter) };
+        my_rwlock::wait_while(counter, seen, remaining);
     }
 }
 
+/// Pops a value and writes it only if the row version is still `version`,
+/// returns the new version of the row
+#[wasm_bindgen]
+pub fn table_insert_if_version(table: usize, col: usize, row_id: u32, version: u32) -> i32 {
+    let Some(value) = pop_from_something_stack() else {
+        return StoreError::NotFound.code();
+    };
+    let op = Operation::InsertIfVersion {
+        table_id: table,
+        row_id,
+        value,
+        index: col,
+        version,
+    };
+    let result = GLOBALS.with_db_mut(|db| {
+        db.operation(op)?;
+        return db.get_row_version(table, row_id);
+    });
+    return match result {
+        Ok(version) => version as i32,
+        Err(err) => err.code(),
+    };
+}
+
+/// Atomic operations are applied right away even while batching,
+/// because their result is pushed to the js stack
+fn apply_and_push_result(op: Operation) -> i32 {
+    let result = GLOBALS.with_db_mut(|db| db.operation(op));
+    return push_result(result);
+}
+
+fn push_result(result: Result<Something, StoreError>) -> i32 {
+    return match result {
+        Ok(value) => {
+            push_to_js_stack(&value);
+            0
+        }
+        Err(err) => err.code(),
+    };
+}
+
+#[wasm_bindgen]
+pub fn table_add_i32(table: usize, col: usize, row_id: u32, value: i32) -> i32 {
+    return apply_and_push_result(Operation::AddInt {
+        table_id: table,
+        row_id,
+        index: col,
+        value,
+    });
+}
+
+#[wasm_bindgen]
+pub fn table_add_f64(table: usize, col: usize, row_id: u32, value: f64) -> i32 {
+    return apply_and_push_result(Operation::AddFloat {
+        table_id: table,
+        row_id,
+        index: col,
+        value,
+    });
+}
+
+/// Pops the new value and then the expected one, pushes the value of the column after the operation.
+/// Returns 1 if the write happened, 0 if the column didn't match the expected one
+#[wasm_bindgen]
+pub fn table_compare_and_swap(table: usize, col: usize, row_id: u32) -> i32 {
+    let (Some(value), Some(expected)) = (pop_from_something_stack(), pop_from_something_stack())
+    else {
+        return StoreError::NotFound.code();
+    };
+    let result = GLOBALS.with_db_mut(|db| db.compare_and_swap(table, row_id, col, expected, value));
+    return match result {
+        Ok((current, swapped)) => {
+            push_to_js_stack(&current);
+            swapped as i32
+        }
+        Err(err) => err.code(),
+    };
+}
+
+/// Pops a string or a blob and appends it to the column
+#[wasm_bindgen]
+pub fn table_append(table: usize, col: usize, row_id: u32) -> i32 {
+    let op = match pop_from_something_stack() {
+        Some(Something::String(value)) => Operation::AppendString {
+            table_id: table,
+            row_id,
+            index: col,
+            value,
+        },
+        Some(Something::Blob(value)) => Operation::AppendBlob {
+            table_id: table,
+            row_id,
+            index: col,
+            value,
+        },
+        _ => return StoreError::TypeMismatch.code(),
+    };
+    return apply_and_push_result(op);
+}
+
 #[wasm_bindgen]
 pub fn something_push_i32_to_stack(value: i32) {
     let something = Something::Int(value);
@@ -248,12 +613,272 @@ pub fn table_with_col_equals(table: usize, col: usize) {
     });
 }
 
+/// Pushes the aggregate of a column over the whole table
+#[wasm_bindgen]
+pub fn table_aggregate(table: usize, col: usize, kind: u8) -> i32 {
+    let Some(aggregate) = Aggregate::from_u8(kind) else {
+        return StoreError::InvalidArgument.code();
+    };
+    let result = GLOBALS.with_db(|db| db.aggregate(table, col, aggregate, None));
+    return push_result(result);
+}
+
+/// Pops a value and pushes the aggregate of a column over the rows where `filter_col` is equal to it
+#[wasm_bindgen]
+pub fn table_aggregate_where(table: usize, col: usize, kind: u8, filter_col: usize) -> i32 {
+    let Some(value) = pop_from_something_stack() else {
+        return StoreError::NotFound.code();
+    };
+    let Some(aggregate) = Aggregate::from_u8(kind) else {
+        return StoreError::InvalidArgument.code();
+    };
+    let result = GLOBALS.with_db(|db| {
+        return db.aggregate(table, col, aggregate, Some((filter_col, value)));
+    });
+    return push_result(result);
+}
+
+/// Pops `n_kinds` aggregate kinds, then for every distinct value of `group_col`
+/// pushes the value followed by those aggregates of `col`. Returns the number of groups
+#[wasm_bindgen]
+pub fn table_group_by(table: usize, group_col: usize, col: usize, n_kinds: usize) -> i32 {
+    let aggregates: Option<Vec<Aggregate>> = pop_indices(n_kinds)
+        .into_iter()
+        .map(|kind| u8::try_from(kind).ok().and_then(Aggregate::from_u8))
+        .collect();
+    let Some(aggregates) = aggregates.filter(|aggregates| aggregates.len() == n_kinds) else {
+        return StoreError::InvalidArgument.code();
+    };
+    let result = GLOBALS.with_db(|db| db.group_by(table, group_col, col, &aggregates));
+    return match result {
+        Ok(groups) => {
+            for (value, results) in &groups {
+                push_to_js_stack(value);
+                results.iter().for_each(push_to_js_stack);
+            }
+            groups.len() as i32
+        }
+        Err(err) => err.code(),
+    };
+}
+
+/// Predicates are built bottom up on a stack, like values on the something stack.
+/// This one pops a value and pushes `col <op> value`
+#[wasm_bindgen]
+pub fn predicate_compare(col: usize, op: u8) {
+    let value = pop_from_something_stack();
+    let (Some(op), Some(value)) = (Comparison::from_u8(op), value) else {
+        invalidate_predicate();
+        push_predicate(Predicate::all());
+        return;
+    };
+    push_predicate(Predicate::Compare { col, op, value });
+}
+
+/// Pops `count` values and pushes `col in (values)`
+#[wasm_bindgen]
+pub fn predicate_in(col: usize, count: usize) {
+    let values: Vec<Something> = (0..count)
+        .filter_map(|_| pop_from_something_stack())
+        .collect();
+    if values.len() < count {
+        invalidate_predicate();
+    }
+    push_predicate(Predicate::In { col, values });
+}
+
+#[wasm_bindgen]
+pub fn predicate_is_null(col: usize) {
+    push_predicate(Predicate::IsNull { col });
+}
+
+/// Pops a string or blob and pushes a predicate matching values starting with it
+#[wasm_bindgen]
+pub fn predicate_prefix(col: usize) {
+    let Some(prefix) = pop_from_something_stack() else {
+        invalidate_predicate();
+        push_predicate(Predicate::all());
+        return;
+    };
+    push_predicate(Predicate::Prefix { col, prefix });
+}
+
+#[wasm_bindgen]
+pub fn predicate_and(count: usize) {
+    push_predicate(Predicate::And(pop_predicates(count)));
+}
+
+#[wasm_bindgen]
+pub fn predicate_or(count: usize) {
+    push_predicate(Predicate::Or(pop_predicates(count)));
+}
+
+#[wasm_bindgen]
+pub fn predicate_not() {
+    let Some(predicate) = pop_predicate() else {
+        invalidate_predicate();
+        push_predicate(Predicate::all());
+        return;
+    };
+    push_predicate(Predicate::Not(Box::new(predicate)));
+}
+
+/// Adds a column to the order of the next `table_query`, the first call is the primary order
+#[wasm_bindgen]
+pub fn query_order_by(col: usize, descending: bool) {
+    QUERY_ORDER.with_borrow_mut(|order| order.push(OrderBy { col, descending }));
+}
+
+/// Pops a predicate, or matches every row if there is none, and pushes the ids of the matching rows.
+/// Rows are sorted by the columns given to `query_order_by`, or by row id.
+/// A negative limit means no limit. Returns the number of ids pushed
+#[wasm_bindgen]
+pub fn table_query(table: usize, limit: i32, offset: usize) -> i32 {
+    let order_by = QUERY_ORDER.take();
+    let predicate = match take_predicate() {
+        Ok(predicate) => predicate,
+        Err(err) => return err.code(),
+    };
+    let mut query = Query::new(predicate);
+    query.order_by = order_by;
+    query.limit = usize::try_from(limit).ok();
+    query.offset = offset;
+    let result = GLOBALS.with_db(|db| db.query(table, &query));
+    return match result {
+        Ok(rows) => {
+            for row_id in &rows {
+                push_to_js_stack(&Something::Int(*row_id as i32));
+            }
+            rows.len() as i32
+        }
+        Err(err) => err.code(),
+    };
+}
+
+/// Pops a predicate and deletes the matching rows, returns how many were deleted.
+/// Applied right away even while batching
+#[wasm_bindgen]
+pub fn table_delete_where(table: usize) -> i32 {
+    let predicate = match take_predicate() {
+        Ok(predicate) => predicate,
+        Err(err) => return err.code(),
+    };
+    return match GLOBALS.with_db_mut(|db| db.delete_where(table, predicate)) {
+        Ok(count) => count as i32,
+        Err(err) => err.code(),
+    };
+}
+
+/// Pops a predicate and `n_cols` `(col, value)` pairs from the stack,
+/// writes them to the matching rows and returns how many were updated.
+/// Applied right away even while batching
+#[wasm_bindgen]
+pub fn table_update_where(table: usize, n_cols: usize) -> i32 {
+    let predicate = take_predicate();
+    let values = match pop_column_pairs(n_cols) {
+        Ok(values) => values,
+        Err(err) => return err.code(),
+    };
+    let predicate = match predicate {
+        Ok(predicate) => predicate,
+        Err(err) => return err.code(),
+    };
+    return match GLOBALS.with_db_mut(|db| db.update_where(table, predicate, values)) {
+        Ok(count) => count as i32,
+        Err(err) => err.code(),
+    };
+}
+
+/// Writes of a value already held by another row in the column fail with a distinct code
+#[wasm_bindgen]
+pub fn table_add_unique(table: usize, col: usize) -> i32 {
+    return status(GLOBALS.with_db_mut(|db| db.add_unique(table, col)));
+}
+
+/// Joins `left_col` of the left table with the right table, `mode` is 0 to match
+/// `right_col`, 1 to match the right row ids and 2 to match the right row keys.
+/// Pushes the left and right row id of every match and returns the number of matches
+#[wasm_bindgen]
+pub fn table_join(left: usize, left_col: usize, right: usize, mode: u8, right_col: usize) -> i32 {
+    let Some(on) = JoinOn::from_u8(mode, right_col) else {
+        return StoreError::InvalidArgument.code();
+    };
+    return match GLOBALS.with_db(|db| db.join(left, left_col, right, on)) {
+        Ok(pairs) => {
+            for (left_id, right_id) in &pairs {
+                push_to_js_stack(&Something::Int(*left_id as i32));
+                push_to_js_stack(&Something::Int(*right_id as i32));
+            }
+            pairs.len() as i32
+        }
+        Err(err) => err.code(),
+    };
+}
+
+/// Like `table_join` but pops `n_right_cols` right column indices and then
+/// `n_left_cols` left column indices, and pushes those columns of every match
+#[wasm_bindgen]
+pub fn table_join_columns(
+    left: usize,
+    left_col: usize,
+    right: usize,
+    mode: u8,
+    right_col: usize,
+    n_left_cols: usize,
+    n_right_cols: usize,
+) -> i32 {
+    let right_cols = pop_indices(n_right_cols);
+    let left_cols = pop_indices(n_left_cols);
+    let Some(on) = JoinOn::from_u8(mode, right_col) else {
+        return StoreError::InvalidArgument.code();
+    };
+    let result = GLOBALS.with_db(|db| {
+        let pairs = db.join(left, left_col, right, on)?;
+        let mut values = Vec::with_capacity(pairs.len() * (left_cols.len() + right_cols.len()));
+        for (left_id, right_id) in &pairs {
+            values.extend(db.get_row_columns(left, *left_id, &left_cols)?);
+            values.extend(db.get_row_columns(right, *right_id, &right_cols)?);
+        }
+        return Ok::<_, StoreError>((pairs.len(), values));
+    });
+    return match result {
+        Ok((count, values)) => {
+            values.iter().for_each(push_to_js_stack);
+            count as i32
+        }
+        Err(err) => err.code(),
+    };
+}
+
+/// Indexes are used by `table_query` to avoid scanning the whole table
+#[wasm_bindgen]
+pub fn table_create_index(table: usize, col: usize) -> i32 {
+    return status(GLOBALS.with_db_mut(|db| db.create_index(table, col)));
+}
+
 #[wasm_bindgen]
 pub fn something_push_f64_to_stack(value: f64) {
     let something = Something::Float(value);
     push_something(something);
 }
 
+#[wasm_bindgen]
+pub fn something_push_row_ref(table: u32, row: u32) {
+    push_something(Something::RowRef { table, row });
+}
+
+/// Pushes the key of the row and returns its tag, used by js to resolve row references
+#[wasm_bindgen]
+pub fn table_get_row_key(table: usize, row_id: u32) -> i32 {
+    return match GLOBALS.with_db(|db| db.get_row_key(table, row_id)) {
+        Ok(key) => {
+            push_to_js_stack(&key);
+            key.tag() as i32
+        }
+        Err(err) => err.code(),
+    };
+}
+
 #[wasm_bindgen]
 pub fn something_push_null_to_stack() {
     let something = Something::Null;
@@ -261,15 +886,13 @@ pub fn something_push_null_to_stack() {
 }
 
 #[wasm_bindgen]
-pub fn delete_row_from_table(table_id: usize, row_id: u32) {
+pub fn delete_row_from_table(table_id: usize, row_id: u32) -> i32 {
     let operation = Operation::RowDelete { table_id, row_id };
     if is_batching() {
         BATCHED_OPERATIONS.with_borrow_mut(|ops| ops.push(operation));
-    } else {
-        GLOBALS.with_db_mut(|db| {
-            db.operation(operation);
-        });
+        return 0;
     }
+    return status(GLOBALS.with_db_mut(|db| db.operation(operation)));
 }
 
 #[wasm_bindgen]
@@ -286,7 +909,8 @@ pub fn end_batch() {
     });
     GLOBALS.with_db_mut(|db| {
         for op in operations {
-            db.operation(op);
+            // there is no one to report errors of batched operations to
+            let _ = db.operation(op);
         }
     });
 
@@ -294,16 +918,16 @@ pub fn end_batch() {
 }
 
 #[wasm_bindgen]
-pub fn table_remove_listener(table_id: usize, listener_id: u32, row_id: u32) {
-    GLOBALS.with_db_mut(|db| {
-        db.remove_listener(table_id, row_id, listener_id);
-    });
+pub fn table_remove_listener(table_id: usize, listener_id: u32, row_id: u32) -> i32 {
+    return status(GLOBALS.with_db_mut(|db| {
+        return db.remove_listener(table_id, row_id, listener_id);
+    }));
 }
 
 #[wasm_bindgen]
 pub fn db_take_notifications() {
     let notifications = GLOBALS.with_db_mut(|db| {
-        return db.take_notifications(worker_id() as u8);
+        return db.take_notifications(worker_id());
     });
 
     for notification in notifications {
@@ -311,15 +935,126 @@ pub fn db_take_notifications() {
     }
 }
 
+/// The calling worker receives every change to the table through `db_take_changes`
+#[wasm_bindgen]
+pub fn table_enable_change_feed(table: usize, with_values: bool) -> i32 {
+    return status(GLOBALS.with_db_mut(|db| db.enable_change_feed(table, with_values)));
+}
+
+#[wasm_bindgen]
+pub fn table_disable_change_feed(table: usize) -> i32 {
+    return status(GLOBALS.with_db_mut(|db| db.disable_change_feed(table)));
+}
+
+/// Pushes a blob with the changes of the tables the worker has a feed on,
+/// see `Change::encode` for the format. Returns the number of changes
+#[wasm_bindgen]
+pub fn db_take_changes() -> i32 {
+    let (count, buf) = GLOBALS.with_db_mut(|db| db.take_changes(worker_id()));
+    push_to_js_stack(&Something::Blob(buf));
+    return count as i32;
+}
+
 #[wasm_bindgen]
 pub fn table_add_listener_to_row(table_id: usize, row_id: u32) -> i32 {
-    fn inner(table_id: usize, row_id: u32) -> Option<ListenerID> {
+    fn inner(table_id: usize, row_id: u32) -> Result<ListenerID, StoreError> {
         let id = GLOBALS.with_db_mut(|db| {
             return db.add_listener_to(table_id, row_id);
         });
         return id;
     }
-    return inner(table_id, row_id).map(|id| id.to_i32()).unwrap_or(-1);
+    return match inner(table_id, row_id) {
+        Ok(id) => id.to_i32(),
+        Err(err) => err.code(),
+    };
+}
+
+/// Returns a listener id notified on every row creation, deletion and write in the table
+/// and when it is cleared
+#[wasm_bindgen]
+pub fn table_add_table_listener(table: usize) -> i32 {
+    return match GLOBALS.with_db_mut(|db| db.add_table_listener(table)) {
+        Ok(id) => id.to_i32(),
+        Err(err) => err.code(),
+    };
+}
+
+#[wasm_bindgen]
+pub fn table_remove_table_listener(table: usize, listener_id: u32) -> i32 {
+    return status(GLOBALS.with_db_mut(|db| db.remove_table_listener(table, listener_id)));
+}
+
+/// Pops a key and returns a listener id notified when the row with that key
+/// is created, written or deleted, the row doesn't need to exist yet
+#[wasm_bindgen]
+pub fn table_add_key_listener(table: usize) -> i32 {
+    let Some(key) = pop_from_something_stack() else {
+        return StoreError::NotFound.code();
+    };
+    return match GLOBALS.with_db_mut(|db| db.add_key_listener(table, key)) {
+        Ok(id) => id.to_i32(),
+        Err(err) => err.code(),
+    };
+}
+
+/// Pops the key the listener was added to
+#[wasm_bindgen]
+pub fn table_remove_key_listener(table: usize, listener_id: u32) -> i32 {
+    let Some(key) = pop_from_something_stack() else {
+        return StoreError::NotFound.code();
+    };
+    return status(GLOBALS.with_db_mut(|db| db.remove_key_listener(table, &key, listener_id)));
+}
+
+/// Pops a predicate and registers a live query, pushes the rows matching now
+/// and returns the listener id, notified whenever rows enter or leave the result
+#[wasm_bindgen]
+pub fn table_subscribe(table: usize) -> i32 {
+    let predicate = match take_predicate() {
+        Ok(predicate) => predicate,
+        Err(err) => return err.code(),
+    };
+    return match GLOBALS.with_db_mut(|db| db.subscribe(table, predicate)) {
+        Ok((listener_id, rows)) => {
+            for row_id in &rows {
+                push_to_js_stack(&Something::Int(*row_id as i32));
+            }
+            listener_id.to_i32()
+        }
+        Err(err) => err.code(),
+    };
+}
+
+#[wasm_bindgen]
+pub fn table_unsubscribe(table: usize, listener_id: u32) -> i32 {
+    return status(GLOBALS.with_db_mut(|db| db.unsubscribe(table, listener_id)));
+}
+
+/// Pushes the rows that entered the result of the live query since the last call
+/// followed by the rows that left it, returns the number of rows that entered
+#[wasm_bindgen]
+pub fn table_take_live_query_changes(table: usize, listener_id: u32) -> i32 {
+    let result = GLOBALS.with_db_mut(|db| db.take_live_query_changes(table, listener_id));
+    return match result {
+        Ok((added, removed)) => {
+            for row_id in added.iter().chain(&removed) {
+                push_to_js_stack(&Something::Int(*row_id as i32));
+            }
+            added.len() as i32
+        }
+        Err(err) => err.code(),
+    };
+}
+
+/// Pops `n_cols` column indices, the listener is only notified of writes to those columns
+#[wasm_bindgen]
+pub fn table_add_column_listener_to_row(table_id: usize, row_id: u32, n_cols: usize) -> i32 {
+    let columns = ColumnMask::from_columns(&pop_indices(n_cols));
+    let result = GLOBALS.with_db_mut(|db| db.add_column_listener_to(table_id, row_id, columns));
+    return match result {
+        Ok(id) => id.to_i32(),
+        Err(err) => err.code(),
+    };
 }
 
 #[wasm_bindgen]
@@ -358,5 +1093,8 @@ fn push_to_js_stack(value: &Something) {
         Something::Float(f) => {
             safe_put_f64(*f);
         }
+        Something::RowRef { table, row } => {
+            safe_put_row_ref(*table, *row);
+        }
     }
 }
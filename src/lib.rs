@@ -1,6 +1,10 @@
 #![cfg_attr(target_arch = "wasm32", feature(stdarch_wasm_atomic_wait))]
+pub mod aggregate;
+pub mod change_feed;
 pub mod extern_functions;
+pub mod index;
 pub mod js_things;
 pub mod my_rwlock;
+pub mod query;
 pub mod storage;
 pub mod value;
@@ -18,7 +18,7 @@ thread_local! {
     static HAS_LOCK: UnsafeCell<bool> = UnsafeCell::new(false);
 }
 
-fn has_global_lock() -> bool {
+pub(crate) fn has_global_lock() -> bool {
     return HAS_LOCK.with(|v| unsafe { *v.get() });
 }
 
@@ -92,9 +92,12 @@ impl<T> DerefMut for WriteGuard<'_, T> {
 
 const UNLOCKED: i32 = 0;
 const WRITE: i32 = -1;
+const NO_OWNER: i32 = -1;
 
 pub struct ThreadLock {
     lock_state: AtomicI32,
+    /// Worker holding the global lock, so it can be released if the worker is terminated
+    global_owner: AtomicI32,
 }
 
 /**
@@ -107,6 +110,7 @@ impl ThreadLock {
     pub const fn new() -> Self {
         ThreadLock {
             lock_state: AtomicI32::new(UNLOCKED),
+            global_owner: AtomicI32::new(NO_OWNER),
         }
     }
 
@@ -173,7 +177,7 @@ impl ThreadLock {
             .compare_exchange(UNLOCKED, WRITE, Ordering::Acquire, Ordering::Relaxed)
             .is_ok();
         if ok {
-            set_global_lock(true);
+            self.set_global_owner();
         }
         return ok;
     }
@@ -183,6 +187,12 @@ impl ThreadLock {
             panic!("Global lock is already held by this thread");
         }
         self.lock_write();
+        self.set_global_owner();
+    }
+
+    fn set_global_owner(&self) {
+        let worker = extern_functions::worker_id() as i32;
+        self.global_owner.store(worker, Ordering::Relaxed);
         set_global_lock(true);
     }
 
@@ -190,11 +200,31 @@ impl ThreadLock {
         if !has_global_lock() {
             panic!("Global lock is not held by this thread");
         }
+        self.global_owner.store(NO_OWNER, Ordering::Relaxed);
         self.lock_state.store(UNLOCKED, Ordering::Release);
         notify(&self.lock_state);
         set_global_lock(false);
     }
 
+    /// Releases the global lock on behalf of a worker that can't do it anymore,
+    /// returns false if the worker didn't hold it
+    pub fn release_global_of(&self, worker_id: usize) -> bool {
+        let released = self
+            .global_owner
+            .compare_exchange(
+                worker_id as i32,
+                NO_OWNER,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .is_ok();
+        if released {
+            self.lock_state.store(UNLOCKED, Ordering::Release);
+            notify(&self.lock_state);
+        }
+        return released;
+    }
+
     pub fn pointer(&self) -> *const i32 {
         return self.lock_state.as_ptr();
     }
@@ -218,7 +248,22 @@ fn wait(lock_state: &AtomicI32) {
     }
 }
 
-fn notify(lock_state: &AtomicI32) {
+/// Blocks the worker while `state` holds `expected`, until notified or for at most
+/// `timeout_ms`. Spurious wakeups are possible, callers check their condition again
+pub(crate) fn wait_while(state: &AtomicI32, expected: i32, timeout_ms: f64) {
+    #[cfg(target_arch = "wasm32")]
+    unsafe {
+        let timeout_ns = (timeout_ms * 1_000_000.0) as i64;
+        std::arch::wasm32::memory_atomic_wait32(state.as_ptr(), expected, timeout_ns);
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let _ = (state, expected, timeout_ms);
+        std::thread::yield_now();
+    }
+}
+
+pub(crate) fn notify(lock_state: &AtomicI32) {
     #[cfg(target_arch = "wasm32")]
     unsafe {
         std::arch::wasm32::memory_atomic_notify(lock_state.as_ptr(), 999);
@@ -0,0 +1,304 @@
+use crate::{index::ColumnIndex, storage::Row, value::Something};
+use std::{
+    cmp::Ordering,
+    collections::{BTreeSet, BinaryHeap, HashMap},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    pub fn from_u8(op: u8) -> Option<Self> {
+        match op {
+            0 => Some(Comparison::Eq),
+            1 => Some(Comparison::Ne),
+            2 => Some(Comparison::Lt),
+            3 => Some(Comparison::Le),
+            4 => Some(Comparison::Gt),
+            5 => Some(Comparison::Ge),
+            _ => None,
+        }
+    }
+
+    /// Ordering comparisons only match values of the same type as the operand
+    fn test(&self, value: &Something, operand: &Something) -> bool {
+        match self {
+            Comparison::Eq => value == operand,
+            Comparison::Ne => value != operand,
+            _ if value.tag() != operand.tag() || value == &Something::Null => false,
+            Comparison::Lt => value < operand,
+            Comparison::Le => value <= operand,
+            Comparison::Gt => value > operand,
+            Comparison::Ge => value >= operand,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Predicate {
+    Compare {
+        col: usize,
+        op: Comparison,
+        value: Something,
+    },
+    In {
+        col: usize,
+        values: Vec<Something>,
+    },
+    IsNull {
+        col: usize,
+    },
+    /// Matches strings or blobs starting with the bytes of `prefix`
+    Prefix {
+        col: usize,
+        prefix: Something,
+    },
+    And(Vec<Predicate>),
+    Or(Vec<Predicate>),
+    Not(Box<Predicate>),
+}
+
+impl Predicate {
+    /// A predicate that matches every row
+    pub fn all() -> Self {
+        return Predicate::And(Vec::new());
+    }
+
+    pub fn matches(&self, row: &Row) -> bool {
+        match self {
+            Predicate::Compare { col, op, value } => op.test(row.get(*col), value),
+            Predicate::In { col, values } => values.contains(row.get(*col)),
+            Predicate::IsNull { col } => row.get(*col) == &Something::Null,
+            Predicate::Prefix { col, prefix } => match (row.get(*col), prefix) {
+                (Something::String(v), Something::String(p)) => v.starts_with(p),
+                (Something::Blob(v), Something::Blob(p)) => v.starts_with(p),
+                _ => false,
+            },
+            Predicate::And(predicates) => predicates.iter().all(|p| p.matches(row)),
+            Predicate::Or(predicates) => predicates.iter().any(|p| p.matches(row)),
+            Predicate::Not(predicate) => !predicate.matches(row),
+        }
+    }
+
+    /// Rows that may match according to the indexes, they still have to be checked with `matches`.
+    /// `None` means the indexes can't narrow the search and the whole table has to be scanned.
+    pub fn candidates(&self, indexes: &HashMap<usize, ColumnIndex>) -> Option<BTreeSet<u32>> {
+        match self {
+            // indexes don't store nulls, rows holding null can only be found by a scan
+            Predicate::Compare { value, .. } if value == &Something::Null => None,
+            Predicate::Compare { col, op, value } => {
+                let rows = indexes.get(col)?.compare(*op, value)?;
+                return Some(rows.into_iter().collect());
+            }
+            Predicate::In { values, .. } if values.contains(&Something::Null) => None,
+            Predicate::In { col, values } => {
+                let index = indexes.get(col)?;
+                return Some(values.iter().flat_map(|v| index.get(v)).collect());
+            }
+            Predicate::Prefix { col, prefix } => {
+                let index = indexes.get(col)?;
+                return Some(index.prefix(prefix).into_iter().collect());
+            }
+            Predicate::And(predicates) => {
+                return predicates
+                    .iter()
+                    .filter_map(|p| p.candidates(indexes))
+                    .min_by_key(|rows| rows.len());
+            }
+            Predicate::Or(predicates) => {
+                let mut rows = BTreeSet::new();
+                for predicate in predicates {
+                    rows.extend(predicate.candidates(indexes)?);
+                }
+                return Some(rows);
+            }
+            Predicate::IsNull { .. } | Predicate::Not(_) => None,
+        }
+    }
+}
+
+/// Result set of a predicate kept up to date as rows change,
+/// with the rows that entered and left it since the changes were last taken
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiveQuery {
+    predicate: Predicate,
+    matching: BTreeSet<u32>,
+    added: BTreeSet<u32>,
+    removed: BTreeSet<u32>,
+}
+
+impl LiveQuery {
+    pub fn new(predicate: Predicate, matching: BTreeSet<u32>) -> Self {
+        return LiveQuery {
+            predicate,
+            matching,
+            added: BTreeSet::new(),
+            removed: BTreeSet::new(),
+        };
+    }
+
+    /// Re-evaluates a single row, `row` is `None` when it was deleted.
+    /// Returns true if the result set changed
+    pub fn update(&mut self, row_id: u32, row: Option<&Row>) -> bool {
+        let matches = row.is_some_and(|row| self.predicate.matches(row));
+        if matches == self.matching.contains(&row_id) {
+            return false;
+        }
+        // a row that leaves and enters again before the changes are taken is not a change
+        if matches {
+            self.matching.insert(row_id);
+            if !self.removed.remove(&row_id) {
+                self.added.insert(row_id);
+            }
+        } else {
+            self.matching.remove(&row_id);
+            if !self.added.remove(&row_id) {
+                self.removed.insert(row_id);
+            }
+        }
+        return true;
+    }
+
+    /// Every row leaves the result set, returns true if it wasn't empty
+    pub fn clear(&mut self) -> bool {
+        let matching = std::mem::take(&mut self.matching);
+        let changed = !matching.is_empty();
+        for row_id in matching {
+            if !self.added.remove(&row_id) {
+                self.removed.insert(row_id);
+            }
+        }
+        return changed;
+    }
+
+    /// Rows added and removed since the last call
+    pub fn take_changes(&mut self) -> (Vec<u32>, Vec<u32>) {
+        let added = std::mem::take(&mut self.added).into_iter().collect();
+        let removed = std::mem::take(&mut self.removed).into_iter().collect();
+        return (added, removed);
+    }
+}
+
+/// What the values of the left column of a join are matched against in the right table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinOn {
+    /// Rows of the right table holding the same value in the column
+    Column(usize),
+    /// The right row with that id, the left column holds ints or row references
+    RowId,
+    /// The right row with that key
+    Key,
+}
+
+impl JoinOn {
+    pub fn from_u8(mode: u8, col: usize) -> Option<Self> {
+        match mode {
+            0 => Some(JoinOn::Column(col)),
+            1 => Some(JoinOn::RowId),
+            2 => Some(JoinOn::Key),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OrderBy {
+    pub col: usize,
+    pub descending: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    pub predicate: Predicate,
+    pub order_by: Vec<OrderBy>,
+    pub limit: Option<usize>,
+    pub offset: usize,
+}
+
+impl Query {
+    pub fn new(predicate: Predicate) -> Self {
+        return Query {
+            predicate,
+            order_by: Vec::new(),
+            limit: None,
+            offset: 0,
+        };
+    }
+
+    /// Orders by the `order_by` columns, ties are broken by row id
+    pub fn compare_rows(&self, a: &Row, b: &Row) -> Ordering {
+        for order in &self.order_by {
+            let ordering = a.get(order.col).cmp(b.get(order.col));
+            let ordering = if order.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            };
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        return a.id.cmp(&b.id);
+    }
+
+    /// Sorts the matching rows, by row id when there is no order, and applies offset and limit.
+    /// With a limit only the first `offset + limit` rows are kept in a heap
+    /// instead of sorting all of them.
+    pub fn sort_page<'a>(&self, rows: impl Iterator<Item = &'a Row>) -> Vec<u32> {
+        let sorted = match self.limit {
+            Some(limit) => {
+                let k = self.offset.saturating_add(limit);
+                let mut heap = BinaryHeap::with_capacity(k.min(1024) + 1);
+                for row in rows {
+                    heap.push(Ranked { row, query: self });
+                    if heap.len() > k {
+                        heap.pop();
+                    }
+                }
+                heap.into_sorted_vec()
+            }
+            None => {
+                let mut ranked: Vec<Ranked> = rows.map(|row| Ranked { row, query: self }).collect();
+                ranked.sort_unstable();
+                ranked
+            }
+        };
+        return sorted
+            .into_iter()
+            .skip(self.offset)
+            .map(|ranked| ranked.row.id)
+            .collect();
+    }
+}
+
+struct Ranked<'a> {
+    row: &'a Row,
+    query: &'a Query,
+}
+
+impl PartialEq for Ranked<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        return self.row.id == other.row.id;
+    }
+}
+
+impl Eq for Ranked<'_> {}
+
+impl PartialOrd for Ranked<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        return Some(self.cmp(other));
+    }
+}
+
+impl Ord for Ranked<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        return self.query.compare_rows(self.row, other.row);
+    }
+}
@@ -1,35 +1,120 @@
-use crate::{extern_functions::worker_id, value::Something};
+use crate::{
+    aggregate::{Accumulator, Aggregate},
+    change_feed::{Change, ChangeFeed, ChangeKind},
+    extern_functions::worker_id,
+    index::ColumnIndex,
+    my_rwlock,
+    query::{JoinOn, LiveQuery, Predicate, Query},
+    value::Something,
+};
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     hash::{BuildHasher, Hash, Hasher},
+    sync::atomic::{AtomicI32, Ordering},
 };
 
 #[derive(Debug, Clone, PartialEq, Eq, Copy, Hash, PartialOrd, Ord)]
 pub struct ListenerID {
     id: u32,
-    worker: u8,
+    worker: usize,
 }
 
 impl ListenerID {
-    fn new(id: u32, worker: u8) -> Self {
+    fn new(id: u32, worker: usize) -> Self {
         ListenerID { id, worker }
     }
 
-    fn is_from_worker(&self, worker_id: u8) -> bool {
+    fn is_from_worker(&self, worker_id: usize) -> bool {
         return self.worker == worker_id;
     }
 
+    /// Ids are handed out per worker and stay below `i32::MAX`, see `Database::new_listener_id`
     pub fn to_i32(&self) -> i32 {
         return self.id as i32;
     }
 }
 
+/// Listeners waiting to be notified, per worker. A listener is pending at most once
+/// however many writes happen before its worker takes the notifications
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+struct PendingNotifications {
+    by_worker: HashMap<usize, HashSet<u32>>,
+    /// Workers with listeners queued since they were last woken up
+    unwoken: HashSet<usize>,
+}
+
+impl PendingNotifications {
+    fn push(&mut self, listener_id: ListenerID) {
+        let pending = self.by_worker.entry(listener_id.worker).or_default();
+        if pending.insert(listener_id.id) {
+            self.unwoken.insert(listener_id.worker);
+        }
+    }
+
+    fn take(&mut self, worker_id: usize) -> Vec<ListenerID> {
+        self.unwoken.remove(&worker_id);
+        let Some(pending) = self.by_worker.remove(&worker_id) else {
+            return Vec::new();
+        };
+        return pending
+            .into_iter()
+            .map(|id| ListenerID::new(id, worker_id))
+            .collect();
+    }
+
+    fn take_unwoken(&mut self, workers: &mut HashSet<usize>) {
+        workers.extend(self.unwoken.drain());
+    }
+}
+
+impl Extend<ListenerID> for PendingNotifications {
+    fn extend<I: IntoIterator<Item = ListenerID>>(&mut self, iter: I) {
+        for listener_id in iter {
+            self.push(listener_id);
+        }
+    }
+}
+
+impl<'a> Extend<&'a ListenerID> for PendingNotifications {
+    fn extend<I: IntoIterator<Item = &'a ListenerID>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().copied());
+    }
+}
+
+/// Columns a row listener is notified of. Columns from 63 on share the last bit,
+/// so a listener on one of them is notified of writes to any of them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ColumnMask(u64);
+
+impl ColumnMask {
+    pub const ALL: ColumnMask = ColumnMask(u64::MAX);
+
+    pub fn from_columns(cols: &[usize]) -> Self {
+        let bits = cols
+            .iter()
+            .fold(0, |bits, col| bits | ColumnMask::bit(*col));
+        return ColumnMask(bits);
+    }
+
+    fn bit(col: usize) -> u64 {
+        return 1 << col.min(63);
+    }
+
+    fn intersects(&self, other: ColumnMask) -> bool {
+        return self.0 & other.0 != 0;
+    }
+}
+
+/// Versions wrap before reaching the sign bit so they can be returned to js as an i32
+const VERSION_MASK: u32 = i32::MAX as u32;
+
 #[derive(Debug, Clone, PartialEq, PartialOrd, Eq, Ord, Hash)]
 pub struct Row {
     values: Vec<Something>,
-    listeners: Option<Vec<ListenerID>>,
+    listeners: Option<Vec<(ListenerID, ColumnMask)>>,
     key: Something,
     pub id: u32,
+    version: u32,
 }
 
 impl Row {
@@ -39,32 +124,69 @@ impl Row {
             listeners: None,
             key,
             id: 0,
+            version: 0,
         }
     }
 
+    pub fn version(&self) -> u32 {
+        return self.version;
+    }
+
+    pub fn key(&self) -> &Something {
+        return &self.key;
+    }
+
     pub fn remove_listener(&mut self, listener_id: ListenerID) -> Option<()> {
         if let Some(listeners) = &mut self.listeners {
-            listeners.retain(|id| *id != listener_id);
+            listeners.retain(|(id, _)| *id != listener_id);
             return Some(());
         }
         return None;
     }
 
-    pub fn add_listener(&mut self, listener_id: ListenerID) {
+    fn remove_worker_listeners(&mut self, worker_id: usize) {
+        if let Some(listeners) = &mut self.listeners {
+            listeners.retain(|(id, _)| !id.is_from_worker(worker_id));
+        }
+    }
+
+    pub fn add_listener(&mut self, listener_id: ListenerID, columns: ColumnMask) {
         if let Some(listeners) = &mut self.listeners {
-            listeners.push(listener_id);
+            listeners.push((listener_id, columns));
         } else {
-            self.listeners = Some(vec![listener_id]);
+            self.listeners = Some(vec![(listener_id, columns)]);
         }
     }
 
-    pub fn notify(&self, arr: &mut Vec<ListenerID>) {
+    /// Notifies every listener, for changes to the whole row
+    fn notify(&self, arr: &mut PendingNotifications) {
+        self.notify_columns(arr, ColumnMask::ALL);
+    }
+
+    /// Notifies the listeners of any of the written columns
+    fn notify_columns(&self, arr: &mut PendingNotifications, written: ColumnMask) {
         if let Some(listeners) = &self.listeners {
-            arr.extend_from_slice(listeners);
+            let listening = listeners
+                .iter()
+                .filter(|(_, cols)| cols.intersects(written));
+            arr.extend(listening.map(|(id, _)| *id));
         }
     }
 
     pub fn insert_at(&mut self, value: Something, index: usize) {
+        self.set(value, index);
+        self.version = (self.version + 1) & VERSION_MASK;
+    }
+
+    /// Writes all the values as a single change, the version is bumped once
+    pub fn insert_many(&mut self, values: impl IntoIterator<Item = (usize, Something)>) {
+        for (index, value) in values {
+            self.set(value, index);
+        }
+        self.version = (self.version + 1) & VERSION_MASK;
+    }
+
+    fn set(&mut self, value: Something, index: usize) {
         if self.values.len() <= index {
             self.values.resize(index + 1, Something::Null);
         }
@@ -83,7 +205,15 @@ impl Row {
 pub struct Database {
     last_table_id: usize,
     tables: Vec<Table>,
-    next_listener_id: u32,
+    /// Next listener id of every worker, indexed by worker id. Reset when the worker is detached
+    next_listener_ids: Vec<u32>,
+    references: Vec<Reference>,
+    /// Bumped whenever a listener of the worker is queued, indexed by worker id.
+    /// Boxed so pointers handed to js stay valid when the vec grows
+    #[allow(clippy::vec_box)]
+    wakeups: Vec<Box<AtomicI32>>,
+    /// Bumped after calls that changed rows, workers waiting for a row change wait on it
+    row_changes: AtomicI32,
 }
 
 #[derive(Debug, Clone)]
@@ -98,22 +228,247 @@ pub enum Operation {
         table_id: usize,
         row_id: u32,
     },
+    /// Adds to an int column, null is treated as 0
+    AddInt {
+        table_id: usize,
+        row_id: u32,
+        index: usize,
+        value: i32,
+    },
+    /// Adds to a float column, null is treated as 0
+    AddFloat {
+        table_id: usize,
+        row_id: u32,
+        index: usize,
+        value: f64,
+    },
+    /// Writes `value` only if the column is equal to `expected`
+    CompareAndSwap {
+        table_id: usize,
+        row_id: u32,
+        index: usize,
+        expected: Something,
+        value: Something,
+    },
+    /// Appends bytes to a string column, null is treated as an empty string
+    AppendString {
+        table_id: usize,
+        row_id: u32,
+        index: usize,
+        value: Vec<u8>,
+    },
+    /// Appends bytes to a blob column, null is treated as an empty blob
+    AppendBlob {
+        table_id: usize,
+        row_id: u32,
+        index: usize,
+        value: Vec<u8>,
+    },
+    /// Writes several columns of a row at once, listeners are notified once
+    InsertMany {
+        table_id: usize,
+        row_id: u32,
+        values: Vec<(usize, Something)>,
+    },
+    /// Inserts only if the row version is still `version`
+    InsertIfVersion {
+        table_id: usize,
+        row_id: u32,
+        value: Something,
+        index: usize,
+        version: u32,
+    },
 }
 
 const NAMES_TABLE_INDEX: usize = 0;
 
+/// Errors that are reported to js as negative status codes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StoreError {
+    /// The table or the row does not exist
+    NotFound,
+    /// The row handle belongs to rows that were removed by a table clear
+    StaleRow,
+    /// The operation does not apply to the type of the value in the column
+    TypeMismatch,
+    /// The row was written since the version the caller has read
+    VersionConflict,
+    /// The row is still referenced by a row of a table declared with `OnDelete::Restrict`
+    Restricted,
+    /// Another row already holds the value in a unique column
+    UniqueViolation,
+    /// The worker registered more listeners than ids fit in an i32
+    ListenerIdsExhausted,
+    /// Waiting was requested on the main thread, which can't block,
+    /// or while holding the global lock, so nothing could change
+    WouldBlock,
+    /// The wait ended before the awaited change
+    TimedOut,
+    /// A mode or kind passed from js is not one of the known values, or a predicate lacks an operand
+    InvalidArgument,
+}
+
+impl StoreError {
+    pub fn code(&self) -> i32 {
+        match self {
+            StoreError::NotFound => -1,
+            StoreError::StaleRow => -2,
+            StoreError::TypeMismatch => -3,
+            StoreError::VersionConflict => -4,
+            StoreError::Restricted => -5,
+            StoreError::UniqueViolation => -6,
+            StoreError::ListenerIdsExhausted => -7,
+            StoreError::WouldBlock => -8,
+            StoreError::TimedOut => -9,
+            StoreError::InvalidArgument => -10,
+        }
+    }
+
+    /// For operations where a row removed by a clear is no different from a deleted one
+    pub(crate) fn stale_as_not_found(self) -> StoreError {
+        return match self {
+            StoreError::StaleRow => StoreError::NotFound,
+            err => err,
+        };
+    }
+}
+
+/// How a table generates keys for rows created without an explicit key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutoKey {
+    None,
+    /// Monotonically increasing `Something::Int` keys.
+    Increment,
+    /// 128-bit ids stored as a 16 byte `Something::Blob`.
+    Random,
+}
+
+impl AutoKey {
+    pub fn from_u8(mode: u8) -> Option<Self> {
+        match mode {
+            0 => Some(AutoKey::None),
+            1 => Some(AutoKey::Increment),
+            2 => Some(AutoKey::Random),
+            _ => None,
+        }
+    }
+}
+
+/// What happens to the rows referencing a row when it is deleted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnDelete {
+    /// The referencing rows are deleted too
+    Cascade,
+    /// The delete fails while the row is referenced
+    Restrict,
+    /// The referencing column is set to null
+    SetNull,
+}
+
+impl OnDelete {
+    pub fn from_u8(mode: u8) -> Option<Self> {
+        match mode {
+            0 => Some(OnDelete::Cascade),
+            1 => Some(OnDelete::Restrict),
+            2 => Some(OnDelete::SetNull),
+            _ => None,
+        }
+    }
+}
+
+/// `from_col` of `from_table` holds row ids of `to_table`,
+/// either as `Something::Int` or as `Something::RowRef`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reference {
+    pub from_table: usize,
+    pub from_col: usize,
+    pub to_table: usize,
+    pub on_delete: OnDelete,
+}
+
+/// Rows to delete as `(table, row)` and columns to set to null as `(table, row, col)`
+struct DeletePlan {
+    rows: HashSet<(usize, u32)>,
+    set_null: Vec<(usize, u32, usize)>,
+}
+
 impl Database {
     pub fn new() -> Self {
         let mut db = Database {
             last_table_id: 0,
             tables: Vec::new(),
-            next_listener_id: 0,
+            next_listener_ids: Vec::new(),
+            references: Vec::new(),
+            wakeups: Vec::new(),
+            row_changes: AtomicI32::new(0),
         };
         db.tables.push(Table::new());
         return db;
     }
 
-    pub fn take_notifications(&mut self, worker_id: u8) -> Vec<i32> {
+    /// Counter of the worker bumped by `wake_workers`, js waits on it with `Atomics.wait`
+    pub fn wakeup_counter(&mut self, worker_id: usize) -> &AtomicI32 {
+        while self.wakeups.len() <= worker_id {
+            self.wakeups.push(Box::new(AtomicI32::new(0)));
+        }
+        return &self.wakeups[worker_id];
+    }
+
+    /// Drops every listener, queued notification and change feed of the worker,
+    /// for workers that were terminated
+    pub fn detach_worker(&mut self, worker_id: usize) {
+        for table in self.tables.iter_mut() {
+            table.detach_worker(worker_id);
+        }
+        // none of its ids are in use anymore, so a worker reusing the id starts over
+        if let Some(next_id) = self.next_listener_ids.get_mut(worker_id) {
+            *next_id = 0;
+        }
+    }
+
+    /// Ids are unique per worker, the worker is part of the id so ids of
+    /// different workers never collide. Ids never wrap around, since an old
+    /// listener could still hold the id
+    fn new_listener_id(&mut self) -> Result<ListenerID, StoreError> {
+        let worker = worker_id();
+        if self.next_listener_ids.len() <= worker {
+            self.next_listener_ids.resize(worker + 1, 0);
+        }
+        let next_id = &mut self.next_listener_ids[worker];
+        if *next_id > i32::MAX as u32 {
+            return Err(StoreError::ListenerIdsExhausted);
+        }
+        let listener_id = ListenerID::new(*next_id, worker);
+        *next_id += 1;
+        return Ok(listener_id);
+    }
+
+    /// Bumps and notifies the counters of the workers that had listeners queued
+    /// since the previous call
+    pub fn wake_workers(&mut self) {
+        let mut workers = HashSet::new();
+        let mut rows_changed = false;
+        for table in self.tables.iter_mut() {
+            table.take_queued_workers(&mut workers);
+            rows_changed |= std::mem::take(&mut table.rows_changed);
+        }
+        if rows_changed {
+            self.row_changes.fetch_add(1, Ordering::SeqCst);
+            my_rwlock::notify(&self.row_changes);
+        }
+        for worker in workers {
+            let counter = self.wakeup_counter(worker);
+            counter.fetch_add(1, Ordering::SeqCst);
+            my_rwlock::notify(counter);
+        }
+    }
+
+    /// Counter bumped by `wake_workers` when rows were created, written, deleted or cleared
+    pub fn row_change_counter(&self) -> &AtomicI32 {
+        return &self.row_changes;
+    }
+
+    pub fn take_notifications(&mut self, worker_id: usize) -> Vec<i32> {
         let notifications: HashSet<i32> = self
             .tables
             .iter_mut()
@@ -126,7 +481,28 @@ impl Database {
         return notifications.into_iter().collect();
     }
 
-    pub fn operation(&mut self, op: Operation) {
+    /// Writes `value` only if the column holds `expected`. Returns the value of the column
+    /// after the call and whether the write happened
+    pub fn compare_and_swap(
+        &mut self,
+        table_id: usize,
+        row_id: u32,
+        index: usize,
+        expected: Something,
+        value: Something,
+    ) -> Result<(Something, bool), StoreError> {
+        let table = self.get_table_mut(table_id).ok_or(StoreError::NotFound)?;
+        return table.update_at(row_id, index, |current| {
+            if current == &expected {
+                return Ok(Some(value));
+            }
+            return Ok(None);
+        });
+    }
+
+    /// Returns the value of the column after the operation,
+    /// or null for operations that don't target a single column
+    pub fn operation(&mut self, op: Operation) -> Result<Something, StoreError> {
         match op {
             Operation::Insert {
                 table_id,
@@ -134,14 +510,107 @@ impl Database {
                 value,
                 index,
             } => {
-                self.get_table_mut(table_id).map(|table| {
-                    return table.insert_at(row_id, value, index);
-                });
+                let table = self.get_table_mut(table_id).ok_or(StoreError::NotFound)?;
+                table.insert_at(row_id, value, index)?;
+                return Ok(Something::Null);
             }
             Operation::RowDelete { table_id, row_id } => {
-                self.get_table_mut(table_id).map(|table| {
-                    table.delete_row(row_id);
+                let table = self.get_table_mut(table_id).ok_or(StoreError::NotFound)?;
+                // a row removed by a clear is gone like a deleted one, and since ids
+                // are never reused a stale id can't delete another row
+                table.get_row(row_id).map_err(StoreError::stale_as_not_found)?;
+                self.delete_rows(table_id, vec![row_id])?;
+                return Ok(Something::Null);
+            }
+            Operation::AddInt {
+                table_id,
+                row_id,
+                index,
+                value,
+            } => {
+                let table = self.get_table_mut(table_id).ok_or(StoreError::NotFound)?;
+                let result = table.update_at(row_id, index, |current| match current {
+                    Something::Int(v) => Ok(Some(Something::Int(v.wrapping_add(value)))),
+                    Something::Null => Ok(Some(Something::Int(value))),
+                    _ => Err(StoreError::TypeMismatch),
+                });
+                return result.map(|(value, _)| value);
+            }
+            Operation::AddFloat {
+                table_id,
+                row_id,
+                index,
+                value,
+            } => {
+                let table = self.get_table_mut(table_id).ok_or(StoreError::NotFound)?;
+                let result = table.update_at(row_id, index, |current| match current {
+                    Something::Float(v) => Ok(Some(Something::Float(v + value))),
+                    Something::Null => Ok(Some(Something::Float(value))),
+                    _ => Err(StoreError::TypeMismatch),
                 });
+                return result.map(|(value, _)| value);
+            }
+            Operation::CompareAndSwap {
+                table_id,
+                row_id,
+                index,
+                expected,
+                value,
+            } => {
+                let (current, _) =
+                    self.compare_and_swap(table_id, row_id, index, expected, value)?;
+                return Ok(current);
+            }
+            Operation::AppendString {
+                table_id,
+                row_id,
+                index,
+                value,
+            } => {
+                let table = self.get_table_mut(table_id).ok_or(StoreError::NotFound)?;
+                let result = table.update_at(row_id, index, |current| match current {
+                    Something::String(v) => Ok(Some(Something::String([v, &value[..]].concat()))),
+                    Something::Null => Ok(Some(Something::String(value))),
+                    _ => Err(StoreError::TypeMismatch),
+                });
+                return result.map(|(value, _)| value);
+            }
+            Operation::AppendBlob {
+                table_id,
+                row_id,
+                index,
+                value,
+            } => {
+                let table = self.get_table_mut(table_id).ok_or(StoreError::NotFound)?;
+                let result = table.update_at(row_id, index, |current| match current {
+                    Something::Blob(v) => Ok(Some(Something::Blob([v, &value[..]].concat()))),
+                    Something::Null => Ok(Some(Something::Blob(value))),
+                    _ => Err(StoreError::TypeMismatch),
+                });
+                return result.map(|(value, _)| value);
+            }
+            Operation::InsertMany {
+                table_id,
+                row_id,
+                values,
+            } => {
+                let table = self.get_table_mut(table_id).ok_or(StoreError::NotFound)?;
+                table.insert_many(row_id, values)?;
+                return Ok(Something::Null);
+            }
+            Operation::InsertIfVersion {
+                table_id,
+                row_id,
+                value,
+                index,
+                version,
+            } => {
+                let table = self.get_table_mut(table_id).ok_or(StoreError::NotFound)?;
+                if table.get_row(row_id)?.version() != version {
+                    return Err(StoreError::VersionConflict);
+                }
+                table.insert_at(row_id, value.clone(), index)?;
+                return Ok(value);
             }
         }
     }
@@ -151,28 +620,155 @@ impl Database {
         table_id: usize,
         row_id: u32,
         listener_id: u32,
-    ) -> Option<()> {
-        let listener_id = ListenerID::new(listener_id, worker_id() as u8);
-        self.tables
-            .get_mut(table_id)?
-            .remove_listener(row_id, listener_id);
-        return Some(());
+    ) -> Result<(), StoreError> {
+        let listener_id = ListenerID::new(listener_id, worker_id());
+        let table = self.tables.get_mut(table_id).ok_or(StoreError::NotFound)?;
+        return table.remove_listener(row_id, listener_id);
     }
 
-    pub fn add_listener_to(&mut self, table_id: usize, row_id: u32) -> Option<ListenerID> {
-        let table = self.tables.get_mut(table_id)?;
-        let listener_id = ListenerID::new(self.next_listener_id, worker_id() as u8);
-        self.next_listener_id += 1;
-        table.add_listener(listener_id, row_id)?;
-        return Some(listener_id);
+    pub fn add_listener_to(
+        &mut self,
+        table_id: usize,
+        row_id: u32,
+    ) -> Result<ListenerID, StoreError> {
+        return self.add_column_listener_to(table_id, row_id, ColumnMask::ALL);
+    }
+
+    /// The listener is only notified of writes to the given columns, and of the row deletion
+    pub fn add_column_listener_to(
+        &mut self,
+        table_id: usize,
+        row_id: u32,
+        columns: ColumnMask,
+    ) -> Result<ListenerID, StoreError> {
+        let listener_id = self.new_listener_id()?;
+        let table = self.tables.get_mut(table_id).ok_or(StoreError::NotFound)?;
+        table.add_listener(listener_id, row_id, columns)?;
+        return Ok(listener_id);
+    }
+
+    /// The listener is notified when rows of the table are created, deleted or written
+    /// and when the table is cleared
+    pub fn add_table_listener(&mut self, table_id: usize) -> Result<ListenerID, StoreError> {
+        let listener_id = self.new_listener_id()?;
+        let table = self.tables.get_mut(table_id).ok_or(StoreError::NotFound)?;
+        table.add_table_listener(listener_id);
+        return Ok(listener_id);
+    }
+
+    pub fn remove_table_listener(
+        &mut self,
+        table_id: usize,
+        listener_id: u32,
+    ) -> Result<(), StoreError> {
+        let listener_id = ListenerID::new(listener_id, worker_id());
+        let table = self.tables.get_mut(table_id).ok_or(StoreError::NotFound)?;
+        return table.remove_table_listener(listener_id);
+    }
+
+    /// The listener is notified when the row with the key is created, written or deleted,
+    /// the row doesn't need to exist yet
+    pub fn add_key_listener(
+        &mut self,
+        table_id: usize,
+        key: Something,
+    ) -> Result<ListenerID, StoreError> {
+        let listener_id = self.new_listener_id()?;
+        let table = self.tables.get_mut(table_id).ok_or(StoreError::NotFound)?;
+        table.add_key_listener(key, listener_id);
+        return Ok(listener_id);
+    }
+
+    pub fn remove_key_listener(
+        &mut self,
+        table_id: usize,
+        key: &Something,
+        listener_id: u32,
+    ) -> Result<(), StoreError> {
+        let listener_id = ListenerID::new(listener_id, worker_id());
+        let table = self.tables.get_mut(table_id).ok_or(StoreError::NotFound)?;
+        return table.remove_key_listener(key, listener_id);
+    }
+
+    pub fn enable_change_feed(
+        &mut self,
+        table_id: usize,
+        with_values: bool,
+    ) -> Result<(), StoreError> {
+        let table = self.tables.get_mut(table_id).ok_or(StoreError::NotFound)?;
+        table.enable_change_feed(worker_id(), with_values);
+        return Ok(());
+    }
+
+    pub fn disable_change_feed(&mut self, table_id: usize) -> Result<(), StoreError> {
+        let table = self.tables.get_mut(table_id).ok_or(StoreError::NotFound)?;
+        table.disable_change_feed(worker_id());
+        return Ok(());
+    }
+
+    /// Changes of every table the worker has a feed on, encoded with `Change::encode`.
+    /// Returns the number of changes and the buffer
+    pub fn take_changes(&mut self, worker_id: usize) -> (usize, Vec<u8>) {
+        let mut count = 0;
+        let mut buf = Vec::new();
+        for (table_id, table) in self.tables.iter_mut().enumerate() {
+            let changes = table.take_changes(worker_id);
+            count += changes.len();
+            for change in &changes {
+                change.encode(table_id, &mut buf);
+            }
+        }
+        return (count, buf);
+    }
+
+    /// Registers a live query on the table, returns its listener id and the rows matching now.
+    /// The listener is notified whenever rows enter or leave the result set
+    pub fn subscribe(
+        &mut self,
+        table_id: usize,
+        predicate: Predicate,
+    ) -> Result<(ListenerID, Vec<u32>), StoreError> {
+        let listener_id = self.new_listener_id()?;
+        let table = self.tables.get_mut(table_id).ok_or(StoreError::NotFound)?;
+        let matching = table.subscribe(listener_id, predicate);
+        return Ok((listener_id, matching));
+    }
+
+    pub fn unsubscribe(&mut self, table_id: usize, listener_id: u32) -> Result<(), StoreError> {
+        let listener_id = ListenerID::new(listener_id, worker_id());
+        let table = self.tables.get_mut(table_id).ok_or(StoreError::NotFound)?;
+        return table.unsubscribe(listener_id);
+    }
+
+    /// Rows that entered and left the result set of the live query since the last call
+    pub fn take_live_query_changes(
+        &mut self,
+        table_id: usize,
+        listener_id: u32,
+    ) -> Result<(Vec<u32>, Vec<u32>), StoreError> {
+        let listener_id = ListenerID::new(listener_id, worker_id());
+        let table = self.tables.get_mut(table_id).ok_or(StoreError::NotFound)?;
+        return table.take_live_query_changes(listener_id);
     }
 
     pub fn create_table(&mut self, name: Something) -> usize {
+        return self.create_table_with_auto_key(name, AutoKey::None, [0, 0]);
+    }
+
+    /// `seed` feeds the generator of `AutoKey::Random` keys. It must come from a real
+    /// source of randomness, wasm has none of its own
+    pub fn create_table_with_auto_key(
+        &mut self,
+        name: Something,
+        auto_key: AutoKey,
+        seed: [u64; 2],
+    ) -> usize {
         self.last_table_id += 1;
         let table_id = self.last_table_id;
-        self.tables.insert(table_id, Table::new());
+        self.tables
+            .insert(table_id, Table::with_auto_key(auto_key, seed));
         self.tables.get_mut(NAMES_TABLE_INDEX).map(|table| {
-            table.insert_at_by_key(&name, Something::Int(table_id as i32), 0);
+            return table.insert_at_by_key(&name, Something::Int(table_id as i32), 0);
         });
         return table_id;
     }
@@ -198,22 +794,134 @@ impl Database {
         return Some(row.id);
     }
 
-    pub fn clear_table(&mut self, table_id: usize) -> Option<()> {
-        let table = self.tables.get_mut(table_id)?;
-        table.clear();
-        return Some(());
+    pub fn clear_table(&mut self, table_id: usize) -> Result<(), StoreError> {
+        let table = self.tables.get(table_id).ok_or(StoreError::NotFound)?;
+        let rows = table.rows.iter().map(|(row_id, _)| row_id).collect();
+        let mut plan = self.plan_deletes(table_id, rows)?;
+        plan.rows.retain(|(t, _)| *t != table_id);
+        self.apply_deletes(plan);
+        self.tables[table_id].clear();
+        return Ok(());
     }
 
-    pub fn get_row_value(&self, table_id: usize, row_id: u32, col: usize) -> Option<Something> {
-        let table = self.tables.get(table_id)?;
+    /// Declares that `from_col` of `from_table` holds row ids of `to_table`,
+    /// enforced when rows of `to_table` are deleted or the table is cleared
+    pub fn add_reference(&mut self, reference: Reference) -> Result<(), StoreError> {
+        if self.tables.get(reference.from_table).is_none()
+            || self.tables.get(reference.to_table).is_none()
+        {
+            return Err(StoreError::NotFound);
+        }
+        if !self.references.contains(&reference) {
+            self.references.push(reference);
+        }
+        return Ok(());
+    }
+
+    /// Deletes the rows and everything that cascades from them,
+    /// nothing is deleted if a restricted reference would be left dangling
+    fn delete_rows(&mut self, table_id: usize, rows: Vec<u32>) -> Result<(), StoreError> {
+        let plan = self.plan_deletes(table_id, rows)?;
+        self.apply_deletes(plan);
+        return Ok(());
+    }
+
+    /// Follows the references of the rows to delete. Cascades are followed in waves,
+    /// every wave looks up each referencing table once for all the rows found so far
+    fn plan_deletes(&self, table_id: usize, rows: Vec<u32>) -> Result<DeletePlan, StoreError> {
+        let mut deletes: HashSet<(usize, u32)> = HashSet::new();
+        let mut wave: HashMap<usize, HashSet<u32>> =
+            HashMap::from([(table_id, rows.into_iter().collect())]);
+        let mut restricted = Vec::new();
+        let mut set_null = Vec::new();
+        loop {
+            for (to_table, rows) in wave.iter_mut() {
+                rows.retain(|row_id| deletes.insert((*to_table, *row_id)));
+            }
+            wave.retain(|_, rows| !rows.is_empty());
+            if wave.is_empty() {
+                break;
+            }
+            let mut next_wave: HashMap<usize, HashSet<u32>> = HashMap::new();
+            for (to_table, rows) in &wave {
+                for reference in self.references.iter().filter(|r| r.to_table == *to_table) {
+                    let from = &self.tables[reference.from_table];
+                    for from_row in from.rows_referencing(reference.from_col, *to_table, rows) {
+                        let row = (reference.from_table, from_row);
+                        match reference.on_delete {
+                            OnDelete::Cascade => {
+                                next_wave.entry(row.0).or_default().insert(row.1);
+                            }
+                            OnDelete::Restrict => restricted.push(row),
+                            OnDelete::SetNull => set_null.push((row.0, row.1, reference.from_col)),
+                        }
+                    }
+                }
+            }
+            wave = next_wave;
+        }
+        // referencing rows that are deleted anyway don't block the delete
+        if restricted.iter().any(|row| !deletes.contains(row)) {
+            return Err(StoreError::Restricted);
+        }
+        set_null.retain(|(table, row, _)| !deletes.contains(&(*table, *row)));
+        return Ok(DeletePlan {
+            rows: deletes,
+            set_null,
+        });
+    }
+
+    /// Rows in the plan were found while planning, so these can't fail
+    fn apply_deletes(&mut self, plan: DeletePlan) {
+        for (table_id, row_id, col) in plan.set_null {
+            let _ = self.tables[table_id].insert_at(row_id, Something::Null, col);
+        }
+        for (table_id, row_id) in plan.rows {
+            let _ = self.tables[table_id].delete_row(row_id);
+        }
+    }
+
+    pub fn get_row_value(
+        &self,
+        table_id: usize,
+        row_id: u32,
+        col: usize,
+    ) -> Result<Something, StoreError> {
+        let table = self.tables.get(table_id).ok_or(StoreError::NotFound)?;
         let row = table.get_row(row_id)?;
-        return Some(row.get(col).clone());
+        return Ok(row.get(col).clone());
     }
 
-    pub fn get_row_values(&self, table_id: usize, row_id: u32) -> Option<Vec<Something>> {
-        let table = self.tables.get(table_id)?;
+    /// Only the given columns of the row, in the order they were asked for
+    pub fn get_row_columns(
+        &self,
+        table_id: usize,
+        row_id: u32,
+        cols: &[usize],
+    ) -> Result<Vec<Something>, StoreError> {
+        let table = self.tables.get(table_id).ok_or(StoreError::NotFound)?;
         let row = table.get_row(row_id)?;
-        return Some(row.iter().cloned().collect());
+        return Ok(cols.iter().map(|col| row.get(*col).clone()).collect());
+    }
+
+    pub fn get_row_key(&self, table_id: usize, row_id: u32) -> Result<Something, StoreError> {
+        let table = self.tables.get(table_id).ok_or(StoreError::NotFound)?;
+        return Ok(table.get_row(row_id)?.key().clone());
+    }
+
+    pub fn get_row_version(&self, table_id: usize, row_id: u32) -> Result<u32, StoreError> {
+        let table = self.tables.get(table_id).ok_or(StoreError::NotFound)?;
+        return Ok(table.get_row(row_id)?.version());
+    }
+
+    pub fn get_row_values(
+        &self,
+        table_id: usize,
+        row_id: u32,
+    ) -> Result<Vec<Something>, StoreError> {
+        let table = self.tables.get(table_id).ok_or(StoreError::NotFound)?;
+        let row = table.get_row(row_id)?;
+        return Ok(row.iter().cloned().collect());
     }
 
     pub fn create_row(&mut self, table_id: usize, key: Something) -> Option<u32> {
@@ -221,6 +929,12 @@ impl Database {
         return Some(table.create_row(key));
     }
 
+    /// Creates a row with a key generated by the table, returning the key and the row id.
+    pub fn create_auto_row(&mut self, table_id: usize) -> Option<(Something, u32)> {
+        let table = self.tables.get_mut(table_id)?;
+        return table.create_auto_row();
+    }
+
     pub fn with_cols_equal_to(
         &self,
         table_id: usize,
@@ -230,21 +944,237 @@ impl Database {
         let table = self.tables.get(table_id)?;
         return Some(table.with_cols_equal_to(col, value));
     }
+
+    /// Aggregates a column over the rows whose `filter` column is equal to the filter value
+    pub fn aggregate(
+        &self,
+        table_id: usize,
+        col: usize,
+        aggregate: Aggregate,
+        filter: Option<(usize, Something)>,
+    ) -> Result<Something, StoreError> {
+        let table = self.tables.get(table_id).ok_or(StoreError::NotFound)?;
+        return Ok(table.aggregate(col, aggregate, filter));
+    }
+
+    /// Each distinct value of `group_col` with the aggregates of `col` over its rows
+    pub fn group_by(
+        &self,
+        table_id: usize,
+        group_col: usize,
+        col: usize,
+        aggregates: &[Aggregate],
+    ) -> Result<Vec<(Something, Vec<Something>)>, StoreError> {
+        let table = self.tables.get(table_id).ok_or(StoreError::NotFound)?;
+        let groups = table
+            .group_by(group_col, col)
+            .into_iter()
+            .map(|(value, acc)| {
+                let results = aggregates.iter().map(|a| acc.result(*a)).collect();
+                return (value.clone(), results);
+            })
+            .collect();
+        return Ok(groups);
+    }
+
+    /// Row ids matching the query predicate, in ascending order if the query has no order
+    pub fn query(&self, table_id: usize, query: &Query) -> Result<Vec<u32>, StoreError> {
+        let table = self.tables.get(table_id).ok_or(StoreError::NotFound)?;
+        return Ok(table.query(query));
+    }
+
+    /// Deletes the rows matching the predicate, returns how many were deleted
+    pub fn delete_where(
+        &mut self,
+        table_id: usize,
+        predicate: Predicate,
+    ) -> Result<usize, StoreError> {
+        let table = self.tables.get(table_id).ok_or(StoreError::NotFound)?;
+        let rows = table.query(&Query::new(predicate));
+        let count = rows.len();
+        self.delete_rows(table_id, rows)?;
+        return Ok(count);
+    }
+
+    /// Writes the values to every row matching the predicate, returns how many were updated
+    pub fn update_where(
+        &mut self,
+        table_id: usize,
+        predicate: Predicate,
+        values: Vec<(usize, Something)>,
+    ) -> Result<usize, StoreError> {
+        let table = self.tables.get_mut(table_id).ok_or(StoreError::NotFound)?;
+        let rows = table.query(&Query::new(predicate));
+        // check unique columns up front so a violation doesn't leave the update half applied,
+        // only the last value written to a column is kept, like in `Table::insert_many`
+        let kept: Vec<&(usize, Something)> = values
+            .iter()
+            .enumerate()
+            .filter(|(i, (col, _))| !values[i + 1..].iter().any(|(later, _)| later == col))
+            .map(|(_, pair)| pair)
+            .collect();
+        let sets_unique = kept
+            .iter()
+            .any(|(col, value)| table.unique.contains(col) && value != &Something::Null);
+        if sets_unique && rows.len() > 1 {
+            return Err(StoreError::UniqueViolation);
+        }
+        for row_id in &rows {
+            for (col, value) in &kept {
+                table.check_unique(*row_id, *col, value)?;
+            }
+        }
+        for row_id in &rows {
+            table.insert_many(*row_id, values.clone())?;
+        }
+        return Ok(rows.len());
+    }
+
+    pub fn create_index(&mut self, table_id: usize, col: usize) -> Result<(), StoreError> {
+        let table = self.tables.get_mut(table_id).ok_or(StoreError::NotFound)?;
+        table.create_index(col);
+        return Ok(());
+    }
+
+    /// Equi-join of `left_col` of the left table with the right table,
+    /// returns the matching `(left row, right row)` pairs sorted by left then right row id.
+    /// Joins on a column use its index when there is one, otherwise a hash table
+    /// of the right column is built first.
+    pub fn join(
+        &self,
+        left: usize,
+        left_col: usize,
+        right: usize,
+        on: JoinOn,
+    ) -> Result<Vec<(u32, u32)>, StoreError> {
+        let left_table = self.tables.get(left).ok_or(StoreError::NotFound)?;
+        let right_table = self.tables.get(right).ok_or(StoreError::NotFound)?;
+        let mut built: HashMap<&Something, Vec<u32>> = HashMap::new();
+        if let JoinOn::Column(col) = on
+            && !right_table.indexes.contains_key(&col)
+        {
+            for (row_id, row) in right_table.rows.iter() {
+                let value = row.get(col);
+                if value != &Something::Null {
+                    built.entry(value).or_default().push(row_id);
+                }
+            }
+        }
+        let mut pairs = Vec::new();
+        for (left_id, left_row) in left_table.rows.iter() {
+            let value = left_row.get(left_col);
+            match on {
+                JoinOn::Column(col) => match right_table.indexes.get(&col) {
+                    Some(index) => pairs.extend(index.get(value).map(|r| (left_id, r))),
+                    None => {
+                        let rows = built.get(value).into_iter().flatten();
+                        pairs.extend(rows.map(|r| (left_id, *r)));
+                    }
+                },
+                JoinOn::RowId => {
+                    let row_id = match value {
+                        Something::Int(id) => *id as u32,
+                        Something::RowRef { table, row } if *table as usize == right => *row,
+                        _ => continue,
+                    };
+                    if right_table.rows.get(&row_id).is_some() {
+                        pairs.push((left_id, row_id));
+                    }
+                }
+                JoinOn::Key => {
+                    if let Some(row_id) = right_table.items.get(value) {
+                        pairs.push((left_id, *row_id));
+                    }
+                }
+            }
+        }
+        pairs.sort_unstable();
+        return Ok(pairs);
+    }
+
+    /// Makes the column unique, writes of a value held by another row fail
+    pub fn add_unique(&mut self, table_id: usize, col: usize) -> Result<(), StoreError> {
+        let table = self.tables.get_mut(table_id).ok_or(StoreError::NotFound)?;
+        return table.add_unique(col);
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct Table {
     items: HashMap<Something, u32>,
-    notifications: Vec<ListenerID>,
+    notifications: PendingNotifications,
+    /// Set when rows change, reset by `Database::wake_workers`
+    rows_changed: bool,
     rows: RowsCollection,
+    indexes: HashMap<usize, ColumnIndex>,
+    /// Columns where no two rows may hold the same non null value, they are always indexed
+    unique: HashSet<usize>,
+    live_queries: Vec<(ListenerID, LiveQuery)>,
+    /// Notified of every row creation, deletion, write and of clears
+    table_listeners: Vec<ListenerID>,
+    /// Notified when the row with the key is created, written or deleted,
+    /// they don't need the row to exist and outlive it
+    key_listeners: HashMap<Something, Vec<ListenerID>>,
+    change_feeds: Vec<ChangeFeed>,
+    auto_key: AutoKey,
+    key_counter: u64,
+    key_seed: [u64; 2],
 }
 
 impl Table {
     pub fn new() -> Self {
+        return Table::with_auto_key(AutoKey::None, [0, 0]);
+    }
+
+    pub fn with_auto_key(auto_key: AutoKey, key_seed: [u64; 2]) -> Self {
         Table {
             items: HashMap::new(),
-            notifications: Vec::new(),
+            notifications: PendingNotifications::default(),
+            rows_changed: false,
             rows: RowsCollection::new(),
+            indexes: HashMap::new(),
+            unique: HashSet::new(),
+            live_queries: Vec::new(),
+            table_listeners: Vec::new(),
+            key_listeners: HashMap::new(),
+            change_feeds: Vec::new(),
+            auto_key,
+            key_counter: 0,
+            key_seed,
+        }
+    }
+
+    fn next_auto_key(&mut self) -> Option<Something> {
+        match self.auto_key {
+            AutoKey::None => None,
+            AutoKey::Increment => {
+                let key = i32::try_from(self.key_counter).ok()?;
+                self.key_counter += 1;
+                Some(Something::Int(key))
+            }
+            AutoKey::Random => {
+                self.key_counter += 1;
+                // each half comes from its own seed, so keys carry the full 128 bits
+                let high = splitmix64(self.key_seed[0].wrapping_add(self.key_counter));
+                let low = splitmix64(self.key_seed[1].wrapping_add(self.key_counter));
+                let mut bytes = Vec::with_capacity(16);
+                bytes.extend(&high.to_le_bytes());
+                bytes.extend(&low.to_le_bytes());
+                Some(Something::Blob(bytes))
+            }
+        }
+    }
+
+    /// Keys created explicitly with `create_row` may collide with generated ones,
+    /// so keys already taken are skipped.
+    pub fn create_auto_row(&mut self) -> Option<(Something, u32)> {
+        loop {
+            let key = self.next_auto_key()?;
+            if self.items.contains_key(&key) {
+                continue;
+            }
+            let id = self.create_row(key.clone());
+            return Some((key, id));
         }
     }
 
@@ -252,25 +1182,89 @@ impl Table {
         for row in self.rows.iter() {
             row.1.notify(&mut self.notifications);
         }
+        for (listener_id, live_query) in self.live_queries.iter_mut() {
+            if live_query.clear() {
+                self.notifications.push(*listener_id);
+            }
+        }
+        self.notifications.extend(&self.table_listeners);
+        for key in self.items.keys() {
+            if let Some(listeners) = self.key_listeners.get(key) {
+                self.notifications.extend(listeners);
+            }
+        }
+        self.record_change(Change::new(ChangeKind::Cleared, 0));
+        self.rows_changed = true;
         self.items = HashMap::new();
-        self.rows = RowsCollection::new();
+        self.rows.clear();
+        for index in self.indexes.values_mut() {
+            index.clear();
+        }
     }
 
-    pub fn remove_listener(&mut self, row_id: u32, listener_id: ListenerID) -> Option<()> {
-        self.rows.get_mut(&row_id)?.remove_listener(listener_id);
-        return Some(());
+    pub fn remove_listener(
+        &mut self,
+        row_id: u32,
+        listener_id: ListenerID,
+    ) -> Result<(), StoreError> {
+        // the listeners of a cleared row were removed with it
+        self.rows
+            .get_checked_mut(row_id)
+            .map_err(StoreError::stale_as_not_found)?
+            .remove_listener(listener_id);
+        return Ok(());
     }
 
-    fn take_notifications(&mut self, worker_id: u8) -> Vec<ListenerID> {
-        let mut v = Vec::new();
-        self.notifications.retain(|l| {
-            if l.is_from_worker(worker_id) {
-                v.push(*l);
-                return false;
-            }
-            return true;
+    fn take_notifications(&mut self, worker_id: usize) -> Vec<ListenerID> {
+        return self.notifications.take(worker_id);
+    }
+
+    fn detach_worker(&mut self, worker_id: usize) {
+        self.take_notifications(worker_id);
+        for row in self.rows.iter_mut() {
+            row.remove_worker_listeners(worker_id);
+        }
+        self.table_listeners
+            .retain(|id| !id.is_from_worker(worker_id));
+        self.key_listeners.retain(|_, listeners| {
+            listeners.retain(|id| !id.is_from_worker(worker_id));
+            return !listeners.is_empty();
         });
-        return v;
+        self.live_queries
+            .retain(|(id, _)| !id.is_from_worker(worker_id));
+        self.disable_change_feed(worker_id);
+    }
+
+    /// Rows whose column holds one of `rows` of `to_table`, as a row id or a row ref.
+    /// Uses the index of the column if there is one, otherwise scans the table once
+    fn rows_referencing(&self, col: usize, to_table: usize, rows: &HashSet<u32>) -> Vec<u32> {
+        if let Some(index) = self.indexes.get(&col) {
+            let mut found = Vec::new();
+            for row in rows {
+                found.extend(index.get(&Something::Int(*row as i32)));
+                found.extend(index.get(&Something::RowRef {
+                    table: to_table as u32,
+                    row: *row,
+                }));
+            }
+            return found;
+        }
+        return self
+            .rows
+            .iter()
+            .filter(|(_, row)| match row.get(col) {
+                Something::Int(id) => *id >= 0 && rows.contains(&(*id as u32)),
+                Something::RowRef { table, row } => {
+                    *table as usize == to_table && rows.contains(row)
+                }
+                _ => false,
+            })
+            .map(|(row_id, _)| row_id)
+            .collect();
+    }
+
+    fn take_queued_workers(&mut self, workers: &mut HashSet<usize>) {
+        self.notifications.take_unwoken(workers);
     }
 
     pub fn with_cols_equal_to(&self, col: usize, value: Something) -> Vec<u32> {
@@ -287,21 +1281,203 @@ impl Table {
             .collect::<Vec<u32>>();
     }
 
-    pub fn add_listener(&mut self, listener_id: ListenerID, row_id: u32) -> Option<()> {
-        let row = self.rows.get_mut(&row_id)?;
-        row.add_listener(listener_id);
-        return Some(());
+    pub fn create_index(&mut self, col: usize) {
+        if self.indexes.contains_key(&col) {
+            return;
+        }
+        let mut index = ColumnIndex::new();
+        for (row_id, row) in self.rows.iter() {
+            index.insert(row.get(col), row_id);
+        }
+        self.indexes.insert(col, index);
+    }
+
+    pub fn query(&self, query: &Query) -> Vec<u32> {
+        let predicate = &query.predicate;
+        let rows: Box<dyn Iterator<Item = &Row>> = match predicate.candidates(&self.indexes) {
+            Some(candidates) => {
+                Box::new(candidates.into_iter().filter_map(|id| self.rows.get(&id)))
+            }
+            None => Box::new(self.rows.iter().map(|(_, row)| row)),
+        };
+        return query.sort_page(rows.filter(|row| predicate.matches(row)));
+    }
+
+    pub fn aggregate(
+        &self,
+        col: usize,
+        aggregate: Aggregate,
+        filter: Option<(usize, Something)>,
+    ) -> Something {
+        let mut acc = Accumulator::new();
+        for (_, row) in self.rows.iter() {
+            if let Some((filter_col, filter_value)) = &filter
+                && row.get(*filter_col) != filter_value
+            {
+                continue;
+            }
+            acc.add(row.get(col));
+        }
+        return acc.result(aggregate);
+    }
+
+    /// One accumulator of `col` per distinct value of `group_col`, ordered by that value.
+    /// Null is a group too
+    pub fn group_by(&self, group_col: usize, col: usize) -> BTreeMap<&Something, Accumulator<'_>> {
+        let mut groups: BTreeMap<&Something, Accumulator> = BTreeMap::new();
+        for (_, row) in self.rows.iter() {
+            groups
+                .entry(row.get(group_col))
+                .or_default()
+                .add(row.get(col));
+        }
+        return groups;
+    }
+
+    pub fn add_table_listener(&mut self, listener_id: ListenerID) {
+        self.table_listeners.push(listener_id);
+    }
+
+    pub fn remove_table_listener(&mut self, listener_id: ListenerID) -> Result<(), StoreError> {
+        let len = self.table_listeners.len();
+        self.table_listeners.retain(|id| *id != listener_id);
+        if self.table_listeners.len() == len {
+            return Err(StoreError::NotFound);
+        }
+        return Ok(());
+    }
+
+    pub fn add_key_listener(&mut self, key: Something, listener_id: ListenerID) {
+        self.key_listeners.entry(key).or_default().push(listener_id);
+    }
+
+    pub fn remove_key_listener(
+        &mut self,
+        key: &Something,
+        listener_id: ListenerID,
+    ) -> Result<(), StoreError> {
+        let listeners = self
+            .key_listeners
+            .get_mut(key)
+            .ok_or(StoreError::NotFound)?;
+        listeners.retain(|id| *id != listener_id);
+        if listeners.is_empty() {
+            self.key_listeners.remove(key);
+        }
+        return Ok(());
+    }
+
+    /// Returns the rows matching the predicate now, the listener is notified when that changes
+    pub fn subscribe(&mut self, listener_id: ListenerID, predicate: Predicate) -> Vec<u32> {
+        let matching = self.query(&Query::new(predicate.clone()));
+        let live_query = LiveQuery::new(predicate, matching.iter().copied().collect());
+        self.live_queries.push((listener_id, live_query));
+        return matching;
+    }
+
+    pub fn unsubscribe(&mut self, listener_id: ListenerID) -> Result<(), StoreError> {
+        let len = self.live_queries.len();
+        self.live_queries.retain(|(id, _)| *id != listener_id);
+        if self.live_queries.len() == len {
+            return Err(StoreError::NotFound);
+        }
+        return Ok(());
+    }
+
+    pub fn take_live_query_changes(
+        &mut self,
+        listener_id: ListenerID,
+    ) -> Result<(Vec<u32>, Vec<u32>), StoreError> {
+        let (_, live_query) = self
+            .live_queries
+            .iter_mut()
+            .find(|(id, _)| *id == listener_id)
+            .ok_or(StoreError::NotFound)?;
+        return Ok(live_query.take_changes());
+    }
+
+    /// The worker receives every change to the table from now on, with the old and new
+    /// values of the written columns when `with_values` is set
+    pub fn enable_change_feed(&mut self, worker: usize, with_values: bool) {
+        self.change_feeds.retain(|feed| feed.worker != worker);
+        self.change_feeds.push(ChangeFeed::new(worker, with_values));
+    }
+
+    pub fn disable_change_feed(&mut self, worker: usize) {
+        self.change_feeds.retain(|feed| feed.worker != worker);
+    }
+
+    fn take_changes(&mut self, worker: usize) -> Vec<Change> {
+        return self
+            .change_feeds
+            .iter_mut()
+            .find(|feed| feed.worker == worker)
+            .map(|feed| std::mem::take(&mut feed.changes))
+            .unwrap_or_default();
+    }
+
+    fn feeds_want_values(&self) -> bool {
+        return self.change_feeds.iter().any(|feed| feed.with_values);
+    }
+
+    fn record_change(&mut self, change: Change) {
+        for feed in self.change_feeds.iter_mut() {
+            feed.push(&change);
+        }
+    }
+
+    /// Called after a row is created, written or deleted. Table listeners are notified
+    /// and live queries re-evaluate only that row
+    fn row_changed(&mut self, row_id: u32) {
+        self.rows_changed = true;
+        self.notifications.extend(&self.table_listeners);
+        let row = self.rows.get(&row_id);
+        if let Some(listeners) = row.and_then(|row| self.key_listeners.get(&row.key)) {
+            self.notifications.extend(listeners);
+        }
+        for (listener_id, live_query) in self.live_queries.iter_mut() {
+            if live_query.update(row_id, row) {
+                self.notifications.push(*listener_id);
+            }
+        }
+    }
+
+    pub fn add_listener(
+        &mut self,
+        listener_id: ListenerID,
+        row_id: u32,
+        columns: ColumnMask,
+    ) -> Result<(), StoreError> {
+        let row = self.rows.get_checked_mut(row_id)?;
+        row.add_listener(listener_id, columns);
+        return Ok(());
     }
 
-    pub fn delete_row(&mut self, row_id: u32) {
+    pub fn delete_row(&mut self, row_id: u32) -> Result<(), StoreError> {
+        self.rows.get_checked(row_id)?;
         if let Some(v) = self.rows.remove(&row_id) {
             v.notify(&mut self.notifications);
+            if let Some(listeners) = self.key_listeners.get(&v.key) {
+                self.notifications.extend(listeners);
+            }
             self.items.remove(&v.key);
+            for (col, index) in self.indexes.iter_mut() {
+                index.remove(v.get(*col), row_id);
+            }
+            let mut change = Change::new(ChangeKind::Deleted, row_id);
+            if self.feeds_want_values() {
+                change.cols = (0..v.values.len()).collect();
+                change.new = vec![Something::Null; v.values.len()];
+                change.old = v.values;
+            }
+            self.record_change(change);
         }
+        self.row_changed(row_id);
+        return Ok(());
     }
 
-    pub fn get_row(&self, row_id: u32) -> Option<&Row> {
-        return self.rows.get(&row_id);
+    pub fn get_row(&self, row_id: u32) -> Result<&Row, StoreError> {
+        return self.rows.get_checked(row_id);
     }
 
     pub fn get_row_by_key(&self, key: &Something) -> Option<&Row> {
@@ -318,33 +1494,151 @@ impl Table {
         let row = Row::new(key.clone());
         let id = self.rows.insert(row);
         self.items.insert(key, id);
+        self.record_change(Change::new(ChangeKind::Created, id));
+        self.row_changed(id);
         return id;
     }
 
-    pub fn insert_at(&mut self, row_id: u32, value: Something, index: usize) {
-        let Some(row) = self.rows.get_mut(&row_id) else {
-            return;
-        };
+    /// Fails when declaring it if the column already holds duplicates
+    pub fn add_unique(&mut self, col: usize) -> Result<(), StoreError> {
+        self.create_index(col);
+        if self.indexes[&col].has_duplicates() {
+            return Err(StoreError::UniqueViolation);
+        }
+        self.unique.insert(col);
+        return Ok(());
+    }
+
+    fn check_unique(&self, row_id: u32, index: usize, value: &Something) -> Result<(), StoreError> {
+        if !self.unique.contains(&index) {
+            return Ok(());
+        }
+        if self.indexes[&index].get(value).any(|other| other != row_id) {
+            return Err(StoreError::UniqueViolation);
+        }
+        return Ok(());
+    }
+
+    pub fn insert_at(
+        &mut self,
+        row_id: u32,
+        value: Something,
+        index: usize,
+    ) -> Result<(), StoreError> {
+        self.rows.get_checked(row_id)?;
+        self.check_unique(row_id, index, &value)?;
+        let with_values = self.feeds_want_values();
+        let row = self.rows.get_checked_mut(row_id)?;
+        if let Some(col_index) = self.indexes.get_mut(&index) {
+            col_index.remove(row.get(index), row_id);
+            col_index.insert(&value, row_id);
+        }
+        let mut change = Change::new(ChangeKind::Written, row_id);
+        change.cols.push(index);
+        if with_values {
+            change.old.push(row.get(index).clone());
+            change.new.push(value.clone());
+        }
         row.insert_at(value, index);
-        row.notify(&mut self.notifications);
+        row.notify_columns(&mut self.notifications, ColumnMask::from_columns(&[index]));
+        self.record_change(change);
+        self.row_changed(row_id);
+        return Ok(());
     }
 
-    pub fn insert_at_by_key(&mut self, key: &Something, value: Something, index: usize) {
-        let row_id = self.items.get(key);
-        if let Some(row_id) = row_id {
-            self.insert_at(*row_id, value, index);
-        } else {
-            self.create_row(key.clone());
-            let row_id = self.items.get(key).unwrap();
-            self.insert_at(*row_id, value, index);
+    pub fn insert_many(
+        &mut self,
+        row_id: u32,
+        values: Vec<(usize, Something)>,
+    ) -> Result<(), StoreError> {
+        self.rows.get_checked(row_id)?;
+        for (i, (index, value)) in values.iter().enumerate() {
+            let overwritten = values[i + 1..].iter().any(|(later, _)| later == index);
+            if !overwritten {
+                self.check_unique(row_id, *index, value)?;
+            }
+        }
+        let with_values = self.feeds_want_values();
+        let row = self.rows.get_checked_mut(row_id)?;
+        // a column may be written more than once, so indexes are updated with the final values
+        let indexed: Vec<usize> = values
+            .iter()
+            .map(|(index, _)| *index)
+            .filter(|index| self.indexes.contains_key(index))
+            .collect();
+        for index in &indexed {
+            self.indexes
+                .get_mut(index)
+                .unwrap()
+                .remove(row.get(*index), row_id);
+        }
+        let written: Vec<usize> = values.iter().map(|(index, _)| *index).collect();
+        let mut change = Change::new(ChangeKind::Written, row_id);
+        change.cols = written.clone();
+        change.cols.sort_unstable();
+        change.cols.dedup();
+        if with_values {
+            change.old = change
+                .cols
+                .iter()
+                .map(|col| row.get(*col).clone())
+                .collect();
+        }
+        row.insert_many(values);
+        for index in &indexed {
+            self.indexes
+                .get_mut(index)
+                .unwrap()
+                .insert(row.get(*index), row_id);
         }
+        if with_values {
+            change.new = change
+                .cols
+                .iter()
+                .map(|col| row.get(*col).clone())
+                .collect();
+        }
+        row.notify_columns(&mut self.notifications, ColumnMask::from_columns(&written));
+        self.record_change(change);
+        self.row_changed(row_id);
+        return Ok(());
+    }
+
+    /// Computes the new value of a column from its current one and writes it,
+    /// when `f` returns `None` nothing is written and no one is notified
+    pub fn update_at(
+        &mut self,
+        row_id: u32,
+        index: usize,
+        f: impl FnOnce(&Something) -> Result<Option<Something>, StoreError>,
+    ) -> Result<(Something, bool), StoreError> {
+        let row = self.rows.get_checked(row_id)?;
+        let Some(value) = f(row.get(index))? else {
+            return Ok((row.get(index).clone(), false));
+        };
+        self.insert_at(row_id, value.clone(), index)?;
+        return Ok((value, true));
+    }
+
+    pub fn insert_at_by_key(
+        &mut self,
+        key: &Something,
+        value: Something,
+        index: usize,
+    ) -> Result<(), StoreError> {
+        let row_id = self.create_row(key.clone());
+        return self.insert_at(row_id, value, index);
     }
 }
 
+/// Row ids are never reused, not even after a clear, so a handle kept by js can
+/// never point to an unrelated row. Each clear starts a new generation of ids,
+/// handles from an older generation are reported as stale.
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct RowsCollection {
     rows: HashMap<u32, Row, FastIDHasher>,
-    next_id: usize,
+    next_id: u32,
+    generation_start: u32,
 }
 
 impl RowsCollection {
@@ -352,23 +1646,41 @@ impl RowsCollection {
         return RowsCollection {
             rows: HashMap::with_hasher(FastIDHasher { state: 0 }),
             next_id: 0,
+            generation_start: 0,
         };
     }
 
     pub fn insert(&mut self, mut row: Row) -> u32 {
-        let id = self.next_id as u32;
+        let id = self.next_id;
         row.id = id;
         self.rows.insert(id, row);
-        self.next_id += 1;
+        self.next_id = id.checked_add(1).expect("row ids exhausted");
         return id;
     }
 
-    pub fn get(&self, id: &u32) -> Option<&Row> {
-        return self.rows.get(id);
+    pub fn clear(&mut self) {
+        self.rows = HashMap::with_hasher(FastIDHasher { state: 0 });
+        self.generation_start = self.next_id;
+    }
+
+    fn missing_error(&self, id: u32) -> StoreError {
+        if id < self.generation_start {
+            return StoreError::StaleRow;
+        }
+        return StoreError::NotFound;
+    }
+
+    pub fn get_checked(&self, id: u32) -> Result<&Row, StoreError> {
+        return self.rows.get(&id).ok_or_else(|| self.missing_error(id));
+    }
+
+    pub fn get_checked_mut(&mut self, id: u32) -> Result<&mut Row, StoreError> {
+        let error = self.missing_error(id);
+        return self.rows.get_mut(&id).ok_or(error);
     }
 
-    pub fn get_mut(&mut self, id: &u32) -> Option<&mut Row> {
-        return self.rows.get_mut(id);
+    pub fn get(&self, id: &u32) -> Option<&Row> {
+        return self.rows.get(id);
     }
 
     pub fn remove(&mut self, id: &u32) -> Option<Row> {
@@ -378,6 +1690,17 @@ impl RowsCollection {
     pub fn iter(&self) -> impl Iterator<Item = (u32, &Row)> {
         return self.rows.iter().map(|(id, row)| (*id, row));
     }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Row> {
+        return self.rows.values_mut();
+    }
+}
+
+fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    return z ^ (z >> 31);
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
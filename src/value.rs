@@ -8,12 +8,17 @@ pub const ROW_TAG: u8 = 4;
 pub const TABLE_TAG: u8 = 5;
 pub const BLOB_TAG: u8 = 6;
 
-#[derive(Debug, Clone, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Something {
     Int(i32),
     Float(f64),
     String(Vec<u8>),
     Blob(Vec<u8>),
+    /// Typed reference to a row of a table, stored instead of a bare row id
+    RowRef {
+        table: u32,
+        row: u32,
+    },
     Null,
 }
 
@@ -32,12 +37,32 @@ impl Something {
             Null => NULL_TAG,
             Float(_) => FLOAT_TAG,
             Blob(_) => BLOB_TAG,
+            RowRef { .. } => ROW_TAG,
         }
     }
 
     pub fn string(s: Vec<u8>) -> Self {
         Something::String(s)
     }
+
+    /// The tag followed by the little endian value, strings and blobs are prefixed by their length
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        use Something::*;
+        buf.push(self.tag());
+        match self {
+            Int(v) => buf.extend_from_slice(&v.to_le_bytes()),
+            Float(v) => buf.extend_from_slice(&v.to_le_bytes()),
+            String(v) | Blob(v) => {
+                buf.extend_from_slice(&(v.len() as u32).to_le_bytes());
+                buf.extend_from_slice(v);
+            }
+            RowRef { table, row } => {
+                buf.extend_from_slice(&table.to_le_bytes());
+                buf.extend_from_slice(&row.to_le_bytes());
+            }
+            Null => {}
+        }
+    }
 }
 
 impl Hash for Something {
@@ -58,12 +83,23 @@ impl Hash for Something {
             Blob(v) => {
                 v.hash(state);
             }
+            RowRef { table, row } => {
+                table.hash(state);
+                row.hash(state);
+            }
             Null => {}
         }
     }
 }
 
 impl Eq for Something {}
+impl PartialOrd for Something {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        return Some(self.cmp(other));
+    }
+}
+
+/// Null is the smallest value, values of different types are ordered by their tag
 impl Ord for Something {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         use Something::*;
@@ -72,10 +108,13 @@ impl Ord for Something {
             (String(a), String(b)) => a.cmp(b),
             (Float(a), Float(b)) => a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal),
             (Blob(a), Blob(b)) => a.cmp(b),
+            (RowRef { table: t1, row: r1 }, RowRef { table: t2, row: r2 }) => {
+                (t1, r1).cmp(&(t2, r2))
+            }
             (Null, Null) => std::cmp::Ordering::Equal,
             (Null, _) => std::cmp::Ordering::Less,
             (_, Null) => std::cmp::Ordering::Greater,
-            _ => panic!("Unreachable comparison case"),
+            _ => self.tag().cmp(&other.tag()),
         }
     }
 }
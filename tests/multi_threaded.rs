@@ -53,3 +53,248 @@ fn multi_threaded() {
     };
     assert_eq!(current_value, (N_REPETITIONS as i32) * 2);
 }
+
+#[test]
+fn multi_threaded_atomic_add() {
+    js::something_push_null_to_stack();
+    let table = js::table_create();
+    const COL: usize = 0;
+    const N_REPETITIONS: usize = 10_000;
+    js::something_push_i32_to_stack(0);
+    let row_id = js::table_create_row(table) as u32;
+    let test = move || {
+        for _ in 0..N_REPETITIONS {
+            assert_eq!(js::table_add_i32(table, COL, row_id, 1), 0);
+            pop_mock_stack().unwrap();
+        }
+    };
+    let t1 = thread::spawn(move || {
+        set_worker_id(1);
+        test();
+    });
+    let t2 = thread::spawn(move || {
+        set_worker_id(2);
+        test();
+    });
+
+    t1.join().unwrap();
+    t2.join().unwrap();
+
+    js::table_get_something(table, COL, row_id);
+    let current_value = pop_mock_stack().unwrap();
+    assert_eq!(current_value, MockValue::Int((N_REPETITIONS as i32) * 2));
+}
+
+#[test]
+fn detached_worker_releases_lock() {
+    thread::spawn(|| {
+        set_worker_id(3);
+        js::lock();
+    })
+    .join()
+    .unwrap();
+    js::worker_detach(3);
+    js::lock();
+    js::unlock();
+}
+
+#[test]
+fn wait_for_row_change() {
+    with_stack_mut(|s| {
+        s.push(MockValue::String("jobs".into()));
+    });
+    js::something_push_string();
+    let table = js::table_create();
+    js::something_push_i32_to_stack(0);
+    let row_id = js::table_create_row(table) as u32;
+    let version = js::table_get_row_version(table, row_id) as u32;
+
+    // the main thread can't block
+    assert_eq!(js::row_wait_change(table, row_id, version, -1.0), -8);
+
+    let waiter = thread::spawn(move || {
+        set_worker_id(4);
+        // holding the lock, nobody else could write the row
+        js::lock();
+        assert_eq!(js::row_wait_change(table, row_id, version, -1.0), -8);
+        js::unlock();
+        let timed_out = js::row_wait_change(table, row_id, version, 10.0);
+        let changed = js::row_wait_change(table, row_id, version, -1.0);
+        return (timed_out, changed);
+    });
+    thread::sleep(std::time::Duration::from_millis(50));
+    js::something_push_i32_to_stack(1);
+    js::table_insert(table, 0, row_id);
+
+    let (timed_out, changed) = waiter.join().unwrap();
+    assert_eq!(timed_out, -9);
+    assert_eq!(changed, js::table_get_row_version(table, row_id));
+    assert_ne!(changed as u32, version);
+}
+
+#[test]
+fn invalid_modes() {
+    with_stack_mut(|s| {
+        s.push(MockValue::String("bad mode".into()));
+    });
+    js::something_push_string();
+    assert_eq!(js::table_create_with_auto_key(9, 0, 0), -10);
+
+    with_stack_mut(|s| {
+        s.push(MockValue::String("modes".into()));
+    });
+    js::something_push_string();
+    let table = js::table_create();
+    assert_eq!(js::table_aggregate(table, 0, 9), -10);
+    js::something_push_i32_to_stack(0);
+    assert_eq!(js::table_aggregate_where(table, 0, 9, 1), -10);
+    assert_eq!(js::table_add_reference(table, 0, table, 9), -10);
+    // 256 must not wrap around to a valid kind
+    for kind in [0, 256] {
+        js::something_push_i32_to_stack(kind);
+    }
+    assert_eq!(js::table_group_by(table, 0, 1, 2), -10);
+    js::something_push_i32_to_stack(-1);
+    assert_eq!(js::table_group_by(table, 0, 1, 1), -10);
+
+    assert_eq!(js::table_join(table, 0, table, 9, 0), -10);
+    js::something_push_i32_to_stack(0);
+    js::something_push_i32_to_stack(1);
+    assert_eq!(js::table_join_columns(table, 0, table, 9, 0, 1, 1), -10);
+    // the column indices were popped, so there is no value left to filter on
+    assert_eq!(js::table_aggregate_where(table, 0, 0, 1), -1);
+}
+
+#[test]
+fn insert_many_pops_counted_pairs() {
+    with_stack_mut(|s| {
+        s.push(MockValue::String("pairs".into()));
+    });
+    js::something_push_string();
+    let table = js::table_create();
+    js::something_push_i32_to_stack(0);
+    let row_id = js::table_create_row(table) as u32;
+
+    // a value left on the stack by another call is not taken as a column
+    js::something_push_i32_to_stack(7);
+    for (col, value) in [(0, 1), (1, 2)] {
+        js::something_push_i32_to_stack(col);
+        js::something_push_i32_to_stack(value);
+    }
+    assert_eq!(js::table_insert_many(table, row_id, 2), 0);
+    for (col, value) in [(0, 1), (1, 2)] {
+        js::table_get_something(table, col, row_id);
+        assert_eq!(pop_mock_stack(), Some(MockValue::Int(value)));
+    }
+    // asking for more pairs than pushed pops nothing
+    assert_eq!(js::table_insert_many(table, row_id, 1), -1);
+    js::table_insert(table, 2, row_id);
+    js::table_get_something(table, 2, row_id);
+    assert_eq!(pop_mock_stack(), Some(MockValue::Int(7)));
+}
+
+#[test]
+fn update_where_pops_counted_pairs() {
+    with_stack_mut(|s| {
+        s.push(MockValue::String("update pairs".into()));
+    });
+    js::something_push_string();
+    let table = js::table_create();
+    js::something_push_i32_to_stack(0);
+    let row_id = js::table_create_row(table) as u32;
+
+    js::something_push_i32_to_stack(7);
+    js::something_push_i32_to_stack(0);
+    js::something_push_i32_to_stack(1);
+    assert_eq!(js::table_update_where(table, 1), 1);
+    js::table_get_something(table, 0, row_id);
+    assert_eq!(pop_mock_stack(), Some(MockValue::Int(1)));
+
+    assert_eq!(js::table_update_where(table, 1), -1);
+    js::table_insert(table, 1, row_id);
+    js::table_get_something(table, 1, row_id);
+    assert_eq!(pop_mock_stack(), Some(MockValue::Int(7)));
+}
+
+#[test]
+fn invalid_predicates() {
+    with_stack_mut(|s| {
+        s.push(MockValue::String("predicates".into()));
+    });
+    js::something_push_string();
+    let table = js::table_create();
+    for value in [1, 2] {
+        js::something_push_i32_to_stack(value);
+        let row_id = js::table_create_row(table) as u32;
+        js::something_push_i32_to_stack(value);
+        js::table_insert(table, 0, row_id);
+    }
+    let count_rows = || {
+        let count = js::table_query(table, -1, 0);
+        with_stack_mut(|s| s.clear());
+        return count;
+    };
+
+    // a missing operand must not turn into `col == null`
+    js::predicate_compare(0, 0);
+    assert_eq!(js::table_delete_where(table), -10);
+    assert_eq!(count_rows(), 2);
+
+    js::something_push_i32_to_stack(1);
+    js::predicate_compare(0, 9);
+    assert_eq!(js::table_query(table, -1, 0), -10);
+
+    js::predicate_prefix(0);
+    js::predicate_is_null(0);
+    js::predicate_or(2);
+    assert_eq!(js::table_subscribe(table), -10);
+
+    js::something_push_i32_to_stack(1);
+    js::predicate_in(0, 2);
+    assert_eq!(js::table_delete_where(table), -10);
+
+    js::predicate_not();
+    assert_eq!(js::table_delete_where(table), -10);
+
+    js::predicate_is_null(0);
+    js::predicate_and(2);
+    assert_eq!(js::table_delete_where(table), -10);
+    assert_eq!(count_rows(), 2);
+
+    // the pairs are still popped
+    js::predicate_compare(0, 0);
+    js::something_push_i32_to_stack(0);
+    js::something_push_i32_to_stack(5);
+    assert_eq!(js::table_update_where(table, 1), -10);
+    assert_eq!(js::table_aggregate_where(table, 0, 0, 0), -1);
+
+    // the next predicate is valid again
+    js::something_push_i32_to_stack(1);
+    js::predicate_compare(0, 0);
+    assert_eq!(js::table_delete_where(table), 1);
+    assert_eq!(count_rows(), 1);
+}
+
+#[test]
+fn select_reads_stale_rows_as_nulls() {
+    with_stack_mut(|s| {
+        s.push(MockValue::String("select".into()));
+    });
+    js::something_push_string();
+    let table = js::table_create();
+    js::something_push_i32_to_stack(1);
+    let stale = js::table_create_row(table);
+    assert_eq!(js::table_clear(table), 0);
+    js::something_push_i32_to_stack(2);
+    let row_id = js::table_create_row(table);
+    js::something_push_i32_to_stack(5);
+    js::table_insert(table, 0, row_id as u32);
+
+    for row in [stale, row_id] {
+        js::something_push_i32_to_stack(row);
+    }
+    js::something_push_i32_to_stack(0);
+    assert_eq!(js::table_get_rows_columns(table, 2, 1), 0);
+    assert_eq!(pop_mock_stack(), Some(MockValue::Int(5)));
+    assert_eq!(pop_mock_stack(), Some(MockValue::Null));
+}
@@ -0,0 +1,261 @@
+use any_store::query::{Comparison, JoinOn, OrderBy, Predicate, Query};
+use any_store::storage::{Database, Operation, StoreError};
+use any_store::value::Something;
+
+fn string(s: &str) -> Something {
+    return Something::String(s.as_bytes().to_vec());
+}
+
+/// Creates a people table with (name, age, team) columns
+fn people(db: &mut Database) -> usize {
+    let table = db.create_table(string("people"));
+    let people = [
+        ("Alice", Something::Int(30), 1),
+        ("Bob", Something::Int(17), 1),
+        ("Anna", Something::Null, 2),
+        ("Carl", Something::Int(45), 3),
+    ];
+    for (i, (name, age, team)) in people.into_iter().enumerate() {
+        let row_id = db.create_row(table, Something::Int(i as i32)).unwrap();
+        let values = [string(name), age, Something::Int(team)];
+        for (index, value) in values.into_iter().enumerate() {
+            let op = Operation::Insert {
+                table_id: table,
+                row_id,
+                value,
+                index,
+            };
+            db.operation(op).unwrap();
+        }
+    }
+    return table;
+}
+
+fn compare(col: usize, op: Comparison, value: Something) -> Predicate {
+    return Predicate::Compare { col, op, value };
+}
+
+#[test]
+fn predicates_with_and_without_indexes() {
+    let mut db = Database::new();
+    let table = people(&mut db);
+    let predicates = [
+        (compare(1, Comparison::Ge, Something::Int(18)), vec![0, 3]),
+        (
+            compare(1, Comparison::Ne, Something::Int(30)),
+            vec![1, 2, 3],
+        ),
+        (Predicate::IsNull { col: 1 }, vec![2]),
+        (compare(1, Comparison::Eq, Something::Null), vec![2]),
+        (
+            Predicate::In {
+                col: 1,
+                values: vec![Something::Int(17), Something::Null],
+            },
+            vec![1, 2],
+        ),
+        (
+            Predicate::Prefix {
+                col: 0,
+                prefix: string("A"),
+            },
+            vec![0, 2],
+        ),
+        (
+            Predicate::And(vec![
+                Predicate::In {
+                    col: 2,
+                    values: vec![Something::Int(1), Something::Int(2)],
+                },
+                Predicate::Not(Box::new(compare(1, Comparison::Lt, Something::Int(18)))),
+            ]),
+            vec![0, 2],
+        ),
+        (
+            Predicate::Or(vec![
+                compare(2, Comparison::Eq, Something::Int(3)),
+                compare(0, Comparison::Eq, string("Bob")),
+            ]),
+            vec![1, 3],
+        ),
+    ];
+    for (predicate, expected) in &predicates {
+        let query = Query::new(predicate.clone());
+        assert_eq!(&db.query(table, &query).unwrap(), expected);
+    }
+    for col in 0..3 {
+        db.create_index(table, col).unwrap();
+    }
+    for (predicate, expected) in &predicates {
+        let query = Query::new(predicate.clone());
+        assert_eq!(&db.query(table, &query).unwrap(), expected);
+    }
+
+    // indexes follow updates and deletes
+    let update = Operation::Insert {
+        table_id: table,
+        row_id: 1,
+        value: Something::Int(18),
+        index: 1,
+    };
+    db.operation(update).unwrap();
+    db.operation(Operation::RowDelete {
+        table_id: table,
+        row_id: 3,
+    })
+    .unwrap();
+    let adults = Query::new(compare(1, Comparison::Ge, Something::Int(18)));
+    assert_eq!(db.query(table, &adults).unwrap(), vec![0, 1]);
+    let mut page = Query::new(Predicate::all());
+    page.limit = Some(2);
+    page.offset = 1;
+    assert_eq!(db.query(table, &page).unwrap(), vec![1, 2]);
+}
+
+#[test]
+fn ordered_queries() {
+    let mut db = Database::new();
+    let table = people(&mut db);
+    let mut query = Query::new(Predicate::all());
+    query.order_by = vec![OrderBy {
+        col: 1,
+        descending: true,
+    }];
+    // nulls are the smallest values
+    assert_eq!(db.query(table, &query).unwrap(), vec![3, 0, 1, 2]);
+
+    query.limit = Some(2);
+    query.offset = 1;
+    assert_eq!(db.query(table, &query).unwrap(), vec![0, 1]);
+
+    let mut by_team = Query::new(compare(1, Comparison::Gt, Something::Int(0)));
+    by_team.order_by = vec![
+        OrderBy {
+            col: 2,
+            descending: false,
+        },
+        OrderBy {
+            col: 1,
+            descending: false,
+        },
+    ];
+    assert_eq!(db.query(table, &by_team).unwrap(), vec![1, 0, 3]);
+    by_team.limit = Some(1);
+    assert_eq!(db.query(table, &by_team).unwrap(), vec![1]);
+}
+
+#[test]
+fn column_projection() {
+    let mut db = Database::new();
+    let table = people(&mut db);
+    let rows = db.query(table, &Query::new(Predicate::IsNull { col: 1 }));
+    let values = db.get_row_columns(table, rows.unwrap()[0], &[2, 0]);
+    assert_eq!(values.unwrap(), vec![Something::Int(2), string("Anna")]);
+    assert_eq!(
+        db.get_row_columns(table, 99, &[0]),
+        Err(StoreError::NotFound)
+    );
+}
+
+#[test]
+fn delete_and_update_where() {
+    let mut db = Database::new();
+    let table = people(&mut db);
+    db.create_index(table, 2).unwrap();
+    let team_one = compare(2, Comparison::Eq, Something::Int(1));
+    let moved = db.update_where(table, team_one.clone(), vec![(2, Something::Int(3))]);
+    assert_eq!(moved, Ok(2));
+    assert_eq!(db.query(table, &Query::new(team_one)).unwrap(), vec![]);
+
+    let team_three = compare(2, Comparison::Eq, Something::Int(3));
+    assert_eq!(db.delete_where(table, team_three.clone()), Ok(3));
+    assert_eq!(
+        db.query(table, &Query::new(Predicate::all())).unwrap(),
+        vec![2]
+    );
+    assert_eq!(db.delete_where(table, team_three), Ok(0));
+}
+
+#[test]
+fn joins() {
+    let mut db = Database::new();
+    let people = people(&mut db);
+    let teams = db.create_table(string("teams"));
+    for (key, team) in [(1, "red"), (3, "blue"), (4, "green")] {
+        let row_id = db.create_row(teams, Something::Int(key)).unwrap();
+        let op = Operation::Insert {
+            table_id: teams,
+            row_id,
+            value: Something::Int(key),
+            index: 0,
+        };
+        db.operation(op).unwrap();
+        let op = Operation::Insert {
+            table_id: teams,
+            row_id,
+            value: string(team),
+            index: 1,
+        };
+        db.operation(op).unwrap();
+    }
+    let expected = vec![(0, 0), (1, 0), (3, 1)];
+    assert_eq!(db.join(people, 2, teams, JoinOn::Key).unwrap(), expected);
+    assert_eq!(
+        db.join(people, 2, teams, JoinOn::Column(0)).unwrap(),
+        expected
+    );
+    db.create_index(teams, 0).unwrap();
+    assert_eq!(
+        db.join(people, 2, teams, JoinOn::Column(0)).unwrap(),
+        expected
+    );
+    // team ids used as row ids of the teams table
+    assert_eq!(
+        db.join(people, 2, teams, JoinOn::RowId).unwrap(),
+        vec![(0, 1), (1, 1), (2, 2)]
+    );
+}
+
+#[test]
+fn live_queries() {
+    let mut db = Database::new();
+    let table = people(&mut db);
+    let adults = compare(1, Comparison::Ge, Something::Int(18));
+    let (listener, rows) = db.subscribe(table, adults).unwrap();
+    assert_eq!(rows, vec![0, 3]);
+    let listener = listener.to_i32() as u32;
+
+    let set_age = |row_id, age| Operation::Insert {
+        table_id: table,
+        row_id,
+        value: Something::Int(age),
+        index: 1,
+    };
+    db.operation(set_age(1, 18)).unwrap();
+    db.operation(set_age(0, 31)).unwrap();
+    db.operation(Operation::RowDelete {
+        table_id: table,
+        row_id: 3,
+    })
+    .unwrap();
+    assert_eq!(db.take_notifications(0), vec![listener as i32]);
+    let changes = db.take_live_query_changes(table, listener).unwrap();
+    assert_eq!(changes, (vec![1], vec![3]));
+
+    // leaving and entering again before the changes are taken is not a change
+    db.operation(set_age(1, 10)).unwrap();
+    db.operation(set_age(1, 20)).unwrap();
+    assert_eq!(
+        db.take_live_query_changes(table, listener),
+        Ok((vec![], vec![]))
+    );
+
+    db.clear_table(table).unwrap();
+    let changes = db.take_live_query_changes(table, listener).unwrap();
+    assert_eq!(changes, (vec![], vec![0, 1]));
+    db.unsubscribe(table, listener).unwrap();
+    assert_eq!(
+        db.take_live_query_changes(table, listener),
+        Err(StoreError::NotFound)
+    );
+}
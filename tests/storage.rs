@@ -0,0 +1,721 @@
+use any_store::aggregate::Aggregate;
+use any_store::change_feed::{ChangeKind, MAX_PENDING_CHANGES};
+use any_store::extern_functions::set_worker_id;
+use any_store::query::{Comparison, Predicate, Query};
+use any_store::storage::{
+    AutoKey, ColumnMask, Database, OnDelete, Operation, Reference, StoreError,
+};
+use any_store::value::Something;
+use std::sync::atomic::Ordering;
+
+fn name(s: &str) -> Something {
+    return Something::String(s.as_bytes().to_vec());
+}
+
+#[test]
+fn auto_increment_keys() {
+    let mut db = Database::new();
+    let table = db.create_table_with_auto_key(name("events"), AutoKey::Increment, [0, 0]);
+    db.create_row(table, Something::Int(1)).unwrap();
+
+    let (k0, r0) = db.create_auto_row(table).unwrap();
+    let (k1, r1) = db.create_auto_row(table).unwrap();
+    let (k2, _) = db.create_auto_row(table).unwrap();
+    assert_eq!(k0, Something::Int(0));
+    // key 1 was taken explicitly, so it is skipped
+    assert_eq!(k1, Something::Int(2));
+    assert_eq!(k2, Something::Int(3));
+    assert_ne!(r0, r1);
+    assert_eq!(db.get_row_by_key(table, &k1), Some(r1));
+}
+
+#[test]
+fn auto_random_keys() {
+    let mut db = Database::new();
+    let table = db.create_table_with_auto_key(name("sessions"), AutoKey::Random, [1, 2]);
+    let (k0, r0) = db.create_auto_row(table).unwrap();
+    let (k1, _) = db.create_auto_row(table).unwrap();
+    let Something::Blob(bytes) = &k0 else {
+        panic!("expected blob key");
+    };
+    assert_eq!(bytes.len(), 16);
+    assert_ne!(k0, k1);
+    assert_eq!(db.get_row_by_key(table, &k0), Some(r0));
+
+    // keys only depend on the seed, which js takes from crypto.getRandomValues
+    let same_seed = db.create_table_with_auto_key(name("same"), AutoKey::Random, [1, 2]);
+    let other_seed = db.create_table_with_auto_key(name("other"), AutoKey::Random, [1, 3]);
+    let (same, _) = db.create_auto_row(same_seed).unwrap();
+    let (other, _) = db.create_auto_row(other_seed).unwrap();
+    assert_eq!(same, k0);
+    let (Something::Blob(same), Something::Blob(other)) = (same, other) else {
+        panic!("expected blob keys");
+    };
+    assert_eq!(same[..8], other[..8]);
+    assert_ne!(same[8..], other[8..]);
+
+    let plain = db.create_table(name("plain"));
+    assert_eq!(db.create_auto_row(plain), None);
+}
+
+#[test]
+fn stale_row_handles_after_clear() {
+    let mut db = Database::new();
+    let table = db.create_table(name("stale"));
+    let old = db.create_row(table, Something::Int(1)).unwrap();
+    let deleted = db.create_row(table, Something::Int(2)).unwrap();
+    db.operation(Operation::RowDelete {
+        table_id: table,
+        row_id: deleted,
+    })
+    .unwrap();
+    db.clear_table(table).unwrap();
+
+    let new = db.create_row(table, Something::Int(1)).unwrap();
+    assert_ne!(old, new);
+    let insert_old = Operation::Insert {
+        table_id: table,
+        row_id: old,
+        value: Something::Int(10),
+        index: 0,
+    };
+    assert_eq!(db.operation(insert_old), Err(StoreError::StaleRow));
+    assert_eq!(db.get_row_value(table, old, 0), Err(StoreError::StaleRow));
+    assert_eq!(db.get_row_value(table, new, 0), Ok(Something::Null));
+    assert_eq!(
+        db.get_row_value(table, new + 1, 0),
+        Err(StoreError::NotFound)
+    );
+    // removing a stale row or its listeners is a no-op like for a deleted row
+    assert_eq!(db.remove_listener(table, old, 0), Err(StoreError::NotFound));
+    let delete_old = Operation::RowDelete {
+        table_id: table,
+        row_id: old,
+    };
+    assert_eq!(db.operation(delete_old), Err(StoreError::NotFound));
+    assert_eq!(db.get_row_value(table, new, 0), Ok(Something::Null));
+}
+
+#[test]
+fn atomic_operations() {
+    let mut db = Database::new();
+    let table = db.create_table(name("atomics"));
+    let row_id = db.create_row(table, Something::Int(1)).unwrap();
+    let add = |value| Operation::AddInt {
+        table_id: table,
+        row_id,
+        index: 0,
+        value,
+    };
+    assert_eq!(db.operation(add(2)), Ok(Something::Int(2)));
+    assert_eq!(db.operation(add(3)), Ok(Something::Int(5)));
+
+    let cas = |expected, value| Operation::CompareAndSwap {
+        table_id: table,
+        row_id,
+        index: 0,
+        expected,
+        value,
+    };
+    let swap = db.operation(cas(Something::Int(4), Something::Int(10)));
+    assert_eq!(swap, Ok(Something::Int(5)));
+    let swap = db.operation(cas(Something::Int(5), Something::Int(10)));
+    assert_eq!(swap, Ok(Something::Int(10)));
+    // the column already holds the new value, but the comparison fails
+    let swap = db.compare_and_swap(table, row_id, 0, Something::Int(4), Something::Int(10));
+    assert_eq!(swap, Ok((Something::Int(10), false)));
+    let swap = db.compare_and_swap(table, row_id, 0, Something::Int(10), Something::Int(1));
+    assert_eq!(swap, Ok((Something::Int(1), true)));
+
+    let append = |value: &str| Operation::AppendString {
+        table_id: table,
+        row_id,
+        index: 1,
+        value: value.as_bytes().to_vec(),
+    };
+    db.operation(append("foo")).unwrap();
+    assert_eq!(db.operation(append("bar")), Ok(name("foobar")));
+
+    let add_float = Operation::AddFloat {
+        table_id: table,
+        row_id,
+        index: 1,
+        value: 1.0,
+    };
+    assert_eq!(db.operation(add_float), Err(StoreError::TypeMismatch));
+}
+
+#[test]
+fn row_versions() {
+    let mut db = Database::new();
+    let table = db.create_table(name("versions"));
+    let row_id = db.create_row(table, Something::Int(1)).unwrap();
+    assert_eq!(db.get_row_version(table, row_id), Ok(0));
+
+    let insert_if = |version| Operation::InsertIfVersion {
+        table_id: table,
+        row_id,
+        value: Something::Int(1),
+        index: 0,
+        version,
+    };
+    db.operation(insert_if(0)).unwrap();
+    assert_eq!(db.get_row_version(table, row_id), Ok(1));
+    assert_eq!(db.operation(insert_if(0)), Err(StoreError::VersionConflict));
+    assert_eq!(db.get_row_version(table, row_id), Ok(1));
+
+    let insert_many = Operation::InsertMany {
+        table_id: table,
+        row_id,
+        values: vec![
+            (0, Something::Int(2)),
+            (2, name("two")),
+            (0, Something::Int(3)),
+        ],
+    };
+    db.operation(insert_many).unwrap();
+    // the whole write is a single change
+    assert_eq!(db.get_row_version(table, row_id), Ok(2));
+    assert_eq!(
+        db.get_row_values(table, row_id),
+        Ok(vec![Something::Int(3), Something::Null, name("two")])
+    );
+}
+
+#[test]
+fn aggregates() {
+    let mut db = Database::new();
+    let table = db.create_table(name("orders"));
+    let rows = [(1, 10.0), (1, 20.0), (2, 5.5)];
+    for (i, (customer, total)) in rows.iter().enumerate() {
+        let row_id = db.create_row(table, Something::Int(i as i32)).unwrap();
+        let insert = |value, index| Operation::Insert {
+            table_id: table,
+            row_id,
+            value,
+            index,
+        };
+        db.operation(insert(Something::Int(*customer), 0)).unwrap();
+        db.operation(insert(Something::Float(*total), 1)).unwrap();
+    }
+    db.create_row(table, Something::Int(10)).unwrap();
+
+    let agg = |aggregate, filter| db.aggregate(table, 1, aggregate, filter).unwrap();
+    assert_eq!(agg(Aggregate::Count, None), Something::Int(3));
+    assert_eq!(agg(Aggregate::Sum, None), Something::Float(35.5));
+    assert_eq!(agg(Aggregate::Min, None), Something::Float(5.5));
+    assert_eq!(agg(Aggregate::Max, None), Something::Float(20.0));
+    let customer_1 = Some((0, Something::Int(1)));
+    assert_eq!(agg(Aggregate::Avg, customer_1), Something::Float(15.0));
+    let nobody = Some((0, Something::Int(3)));
+    assert_eq!(agg(Aggregate::Sum, nobody.clone()), Something::Null);
+    assert_eq!(agg(Aggregate::Count, nobody), Something::Int(0));
+}
+
+#[test]
+fn foreign_keys() {
+    let mut db = Database::new();
+    let teams = db.create_table(name("teams"));
+    let people = db.create_table(name("people"));
+    let badges = db.create_table(name("badges"));
+    db.add_reference(Reference {
+        from_table: people,
+        from_col: 0,
+        to_table: teams,
+        on_delete: OnDelete::Cascade,
+    })
+    .unwrap();
+    db.add_reference(Reference {
+        from_table: badges,
+        from_col: 0,
+        to_table: people,
+        on_delete: OnDelete::SetNull,
+    })
+    .unwrap();
+    let team = db.create_row(teams, Something::Int(0)).unwrap();
+    let other_team = db.create_row(teams, Something::Int(1)).unwrap();
+    let person = db.create_row(people, Something::Int(0)).unwrap();
+    let badge = db.create_row(badges, Something::Int(0)).unwrap();
+    let link = |table_id, row_id, target: u32| Operation::Insert {
+        table_id,
+        row_id,
+        value: Something::Int(target as i32),
+        index: 0,
+    };
+    db.operation(link(people, person, team)).unwrap();
+    db.operation(link(badges, badge, person)).unwrap();
+
+    db.operation(Operation::RowDelete {
+        table_id: teams,
+        row_id: team,
+    })
+    .unwrap();
+    assert_eq!(
+        db.get_row_value(people, person, 0),
+        Err(StoreError::NotFound)
+    );
+    assert_eq!(db.get_row_value(badges, badge, 0), Ok(Something::Null));
+
+    db.add_reference(Reference {
+        from_table: badges,
+        from_col: 1,
+        to_table: teams,
+        on_delete: OnDelete::Restrict,
+    })
+    .unwrap();
+    let insert = Operation::Insert {
+        table_id: badges,
+        row_id: badge,
+        value: Something::Int(other_team as i32),
+        index: 1,
+    };
+    db.operation(insert).unwrap();
+    assert_eq!(db.clear_table(teams), Err(StoreError::Restricted));
+    assert_eq!(db.get_row_version(teams, other_team), Ok(0));
+    db.operation(Operation::RowDelete {
+        table_id: badges,
+        row_id: badge,
+    })
+    .unwrap();
+    db.clear_table(teams).unwrap();
+}
+
+#[test]
+fn unique_columns() {
+    let mut db = Database::new();
+    let table = db.create_table(name("users"));
+    let alice = db.create_row(table, Something::Int(0)).unwrap();
+    let bob = db.create_row(table, Something::Int(1)).unwrap();
+    let email = |row_id, value: &str| Operation::Insert {
+        table_id: table,
+        row_id,
+        value: name(value),
+        index: 0,
+    };
+    db.operation(email(alice, "a@x")).unwrap();
+    db.operation(email(bob, "a@x")).unwrap();
+    assert_eq!(db.add_unique(table, 0), Err(StoreError::UniqueViolation));
+
+    db.operation(email(bob, "b@x")).unwrap();
+    db.add_unique(table, 0).unwrap();
+    assert_eq!(
+        db.operation(email(bob, "a@x")),
+        Err(StoreError::UniqueViolation)
+    );
+    // rewriting its own value is fine
+    db.operation(email(alice, "a@x")).unwrap();
+    assert_eq!(db.get_row_value(table, bob, 0), Ok(name("b@x")));
+    let insert_many = Operation::InsertMany {
+        table_id: table,
+        row_id: bob,
+        values: vec![(0, name("a@x")), (0, name("c@x"))],
+    };
+    db.operation(insert_many).unwrap();
+    assert_eq!(db.get_row_value(table, bob, 0), Ok(name("c@x")));
+    // update_where also only checks the last value of a column
+    let is_bob = Predicate::Compare {
+        col: 0,
+        op: Comparison::Eq,
+        value: name("c@x"),
+    };
+    let values = vec![(0, name("a@x")), (0, name("d@x"))];
+    assert_eq!(db.update_where(table, is_bob, values), Ok(1));
+    assert_eq!(db.get_row_value(table, bob, 0), Ok(name("d@x")));
+    let values = vec![(0, name("a@x")), (0, Something::Null)];
+    assert_eq!(db.update_where(table, Predicate::all(), values), Ok(2));
+}
+
+#[test]
+fn row_refs() {
+    let mut db = Database::new();
+    let teams = db.create_table(name("teams"));
+    let people = db.create_table(name("people"));
+    db.add_reference(Reference {
+        from_table: people,
+        from_col: 0,
+        to_table: teams,
+        on_delete: OnDelete::SetNull,
+    })
+    .unwrap();
+    let team = db.create_row(teams, name("red")).unwrap();
+    let person = db.create_row(people, Something::Int(0)).unwrap();
+    let team_ref = Something::RowRef {
+        table: teams as u32,
+        row: team,
+    };
+    let insert = Operation::Insert {
+        table_id: people,
+        row_id: person,
+        value: team_ref.clone(),
+        index: 0,
+    };
+    db.operation(insert).unwrap();
+    assert_eq!(
+        db.with_cols_equal_to(people, 0, team_ref),
+        Some(vec![person])
+    );
+    assert_eq!(db.get_row_key(teams, team), Ok(name("red")));
+
+    db.operation(Operation::RowDelete {
+        table_id: teams,
+        row_id: team,
+    })
+    .unwrap();
+    assert_eq!(db.get_row_value(people, person, 0), Ok(Something::Null));
+}
+
+#[test]
+fn group_by() {
+    let mut db = Database::new();
+    let table = db.create_table(name("events"));
+    let events = [
+        ("click", 3),
+        ("view", 1),
+        ("click", 5),
+        ("view", 2),
+        ("click", 1),
+    ];
+    for (i, (kind, ms)) in events.into_iter().enumerate() {
+        let row_id = db.create_row(table, Something::Int(i as i32)).unwrap();
+        for (index, value) in [name(kind), Something::Int(ms)].into_iter().enumerate() {
+            let op = Operation::Insert {
+                table_id: table,
+                row_id,
+                value,
+                index,
+            };
+            db.operation(op).unwrap();
+        }
+    }
+    let aggregates = [Aggregate::Count, Aggregate::Sum, Aggregate::Max];
+    let groups = db.group_by(table, 0, 1, &aggregates).unwrap();
+    assert_eq!(
+        groups,
+        vec![
+            (
+                name("click"),
+                vec![Something::Int(3), Something::Float(9.0), Something::Int(5)]
+            ),
+            (
+                name("view"),
+                vec![Something::Int(2), Something::Float(3.0), Something::Int(2)]
+            ),
+        ]
+    );
+}
+
+#[test]
+fn table_listeners() {
+    let mut db = Database::new();
+    let table = db.create_table(name("log"));
+    let listener = db.add_table_listener(table).unwrap().to_i32();
+    let row_id = db.create_row(table, Something::Int(0)).unwrap();
+    assert_eq!(db.take_notifications(0), vec![listener]);
+    db.operation(Operation::RowDelete {
+        table_id: table,
+        row_id,
+    })
+    .unwrap();
+    assert_eq!(db.take_notifications(0), vec![listener]);
+    db.clear_table(table).unwrap();
+    assert_eq!(db.take_notifications(0), vec![listener]);
+
+    db.remove_table_listener(table, listener as u32).unwrap();
+    db.create_row(table, Something::Int(1)).unwrap();
+    assert_eq!(db.take_notifications(0), vec![]);
+}
+
+#[test]
+fn key_listeners() {
+    let mut db = Database::new();
+    let table = db.create_table(name("settings"));
+    let key = name("session");
+    let listener = db.add_key_listener(table, key.clone()).unwrap().to_i32();
+    db.create_row(table, Something::Int(0)).unwrap();
+    assert_eq!(db.take_notifications(0), vec![]);
+
+    for _ in 0..2 {
+        let row_id = db.create_row(table, key.clone()).unwrap();
+        assert_eq!(db.take_notifications(0), vec![listener]);
+        let insert = Operation::Insert {
+            table_id: table,
+            row_id,
+            value: Something::Int(1),
+            index: 0,
+        };
+        db.operation(insert).unwrap();
+        assert_eq!(db.take_notifications(0), vec![listener]);
+        db.operation(Operation::RowDelete {
+            table_id: table,
+            row_id,
+        })
+        .unwrap();
+        assert_eq!(db.take_notifications(0), vec![listener]);
+    }
+
+    db.remove_key_listener(table, &key, listener as u32)
+        .unwrap();
+    db.create_row(table, key).unwrap();
+    assert_eq!(db.take_notifications(0), vec![]);
+}
+
+#[test]
+fn column_listeners() {
+    let mut db = Database::new();
+    let table = db.create_table(name("users"));
+    let row_id = db.create_row(table, Something::Int(0)).unwrap();
+    let name_listener = db
+        .add_column_listener_to(table, row_id, ColumnMask::from_columns(&[0]))
+        .unwrap()
+        .to_i32();
+    let any_listener = db.add_listener_to(table, row_id).unwrap().to_i32();
+    let write = |index| Operation::Insert {
+        table_id: table,
+        row_id,
+        value: Something::Int(1),
+        index,
+    };
+    // heartbeat on another column
+    db.operation(write(1)).unwrap();
+    assert_eq!(db.take_notifications(0), vec![any_listener]);
+    db.operation(write(0)).unwrap();
+    let mut notified = db.take_notifications(0);
+    notified.sort();
+    assert_eq!(notified, vec![name_listener, any_listener]);
+}
+
+#[test]
+fn change_feed() {
+    let mut db = Database::new();
+    let table = db.create_table(name("users"));
+    db.enable_change_feed(table, true).unwrap();
+    let row_id = db.create_row(table, Something::Int(0)).unwrap();
+    db.operation(Operation::Insert {
+        table_id: table,
+        row_id,
+        value: Something::Int(7),
+        index: 1,
+    })
+    .unwrap();
+
+    let (count, buf) = db.take_changes(0);
+    assert_eq!(count, 2);
+    let mut expected = Vec::new();
+    expected.extend_from_slice(&(table as u32).to_le_bytes());
+    expected.extend_from_slice(&row_id.to_le_bytes());
+    expected.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+    expected.extend_from_slice(&(table as u32).to_le_bytes());
+    expected.extend_from_slice(&row_id.to_le_bytes());
+    expected.extend_from_slice(&[1, 1, 0, 0, 0, 1, 0, 0, 0, 1]);
+    Something::Null.encode(&mut expected);
+    Something::Int(7).encode(&mut expected);
+    assert_eq!(buf, expected);
+    assert_eq!(db.take_changes(0), (0, vec![]));
+
+    db.enable_change_feed(table, false).unwrap();
+    db.operation(Operation::RowDelete {
+        table_id: table,
+        row_id,
+    })
+    .unwrap();
+    let (count, buf) = db.take_changes(0);
+    assert_eq!(count, 1);
+    assert_eq!(buf[8], 2);
+    assert_eq!(buf.len(), 14);
+
+    db.disable_change_feed(table).unwrap();
+    db.create_row(table, Something::Int(1)).unwrap();
+    assert_eq!(db.take_changes(0), (0, vec![]));
+}
+
+#[test]
+fn wakeup_counters() {
+    let mut db = Database::new();
+    let table = db.create_table(name("users"));
+    db.add_table_listener(table).unwrap();
+    assert_eq!(db.wakeup_counter(0).load(Ordering::SeqCst), 0);
+
+    let row_id = db.create_row(table, Something::Int(0)).unwrap();
+    db.wake_workers();
+    assert_eq!(db.wakeup_counter(0).load(Ordering::SeqCst), 1);
+    // already pending notifications don't wake the worker again
+    db.wake_workers();
+    assert_eq!(db.wakeup_counter(0).load(Ordering::SeqCst), 1);
+
+    db.take_notifications(0);
+    db.operation(Operation::RowDelete {
+        table_id: table,
+        row_id,
+    })
+    .unwrap();
+    db.wake_workers();
+    assert_eq!(db.wakeup_counter(0).load(Ordering::SeqCst), 2);
+    assert_eq!(db.wakeup_counter(1).load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn detach_worker() {
+    let mut db = Database::new();
+    let table = db.create_table(name("users"));
+    let row_id = db.create_row(table, Something::Int(0)).unwrap();
+    set_worker_id(1);
+    db.add_table_listener(table).unwrap();
+    db.add_key_listener(table, Something::Int(0)).unwrap();
+    db.add_listener_to(table, row_id).unwrap();
+    db.enable_change_feed(table, false).unwrap();
+    set_worker_id(0);
+    let listener = db.add_table_listener(table).unwrap().to_i32();
+
+    let insert = Operation::Insert {
+        table_id: table,
+        row_id,
+        value: Something::Int(1),
+        index: 0,
+    };
+    db.operation(insert.clone()).unwrap();
+    db.detach_worker(1);
+    assert_eq!(db.take_notifications(1), vec![]);
+    assert_eq!(db.take_changes(1), (0, vec![]));
+    assert_eq!(db.take_notifications(0), vec![listener]);
+
+    db.operation(insert).unwrap();
+    assert_eq!(db.take_notifications(1), vec![]);
+    assert_eq!(db.take_notifications(0), vec![listener]);
+}
+
+#[test]
+fn listener_ids_per_worker() {
+    let mut db = Database::new();
+    let table = db.create_table(name("users"));
+    // 300 truncated to a byte is 44
+    set_worker_id(300);
+    let first = db.add_table_listener(table).unwrap().to_i32();
+    set_worker_id(44);
+    let other = db.add_table_listener(table).unwrap().to_i32();
+    let second = db.add_table_listener(table).unwrap().to_i32();
+    set_worker_id(0);
+    assert_eq!((first, other, second), (0, 0, 1));
+
+    db.create_row(table, Something::Int(0)).unwrap();
+    assert_eq!(db.take_notifications(300), vec![first]);
+    let mut notified = db.take_notifications(44);
+    notified.sort();
+    assert_eq!(notified, vec![other, second]);
+
+    db.detach_worker(300);
+    set_worker_id(300);
+    assert_eq!(db.add_table_listener(table).unwrap().to_i32(), 0);
+    set_worker_id(0);
+}
+
+#[test]
+fn coalesced_notifications() {
+    let mut db = Database::new();
+    let table = db.create_table(name("counters"));
+    let row_id = db.create_row(table, Something::Int(0)).unwrap();
+    let row_listener = db.add_listener_to(table, row_id).unwrap().to_i32();
+    let table_listener = db.add_table_listener(table).unwrap().to_i32();
+    let counter_before = db.wakeup_counter(0).load(Ordering::SeqCst);
+
+    for _ in 0..1000 {
+        db.operation(Operation::AddInt {
+            table_id: table,
+            row_id,
+            index: 0,
+            value: 1,
+        })
+        .unwrap();
+        db.wake_workers();
+    }
+    assert_eq!(
+        db.wakeup_counter(0).load(Ordering::SeqCst),
+        counter_before + 1
+    );
+    let mut notified = db.take_notifications(0);
+    notified.sort();
+    assert_eq!(notified, vec![row_listener, table_listener]);
+    assert_eq!(db.take_notifications(0), vec![]);
+}
+
+#[test]
+fn deleting_many_referenced_rows() {
+    const N: u32 = 20_000;
+    let mut db = Database::new();
+    let teams = db.create_table(name("teams"));
+    let people = db.create_table(name("people"));
+    let badges = db.create_table(name("badges"));
+    for (from_table, to_table, on_delete) in [
+        (people, teams, OnDelete::Cascade),
+        (badges, people, OnDelete::SetNull),
+    ] {
+        let reference = Reference {
+            from_table,
+            from_col: 0,
+            to_table,
+            on_delete,
+        };
+        db.add_reference(reference).unwrap();
+    }
+    // badges are looked up through an index, people by scanning the table
+    db.create_index(badges, 0).unwrap();
+    for i in 0..N {
+        let team = db.create_row(teams, Something::Int(i as i32)).unwrap();
+        let person = db.create_row(people, Something::Int(i as i32)).unwrap();
+        let badge = db.create_row(badges, Something::Int(i as i32)).unwrap();
+        let to_team = match i % 2 {
+            0 => Something::Int(team as i32),
+            _ => Something::RowRef {
+                table: teams as u32,
+                row: team,
+            },
+        };
+        for (table_id, row_id, value) in [
+            (teams, team, Something::Int(i as i32)),
+            (people, person, to_team),
+            (badges, badge, Something::Int(person as i32)),
+        ] {
+            let insert = Operation::Insert {
+                table_id,
+                row_id,
+                value,
+                index: 0,
+            };
+            db.operation(insert).unwrap();
+        }
+    }
+
+    let half = Predicate::Compare {
+        col: 0,
+        op: Comparison::Lt,
+        value: Something::Int(N as i32 / 2),
+    };
+    assert_eq!(db.delete_where(teams, half), Ok(N as usize / 2));
+    assert_eq!(db.get_row_value(people, 0, 0), Err(StoreError::NotFound));
+    assert_eq!(db.get_row_value(badges, 0, 0), Ok(Something::Null));
+
+    db.clear_table(teams).unwrap();
+    let remaining = db.query(people, &Query::new(Predicate::all())).unwrap();
+    assert_eq!(remaining, vec![]);
+    let nulls = Predicate::IsNull { col: 0 };
+    let unlinked = db.query(badges, &Query::new(nulls)).unwrap();
+    assert_eq!(unlinked.len(), N as usize);
+}
+
+#[test]
+fn change_feed_overflow() {
+    let mut db = Database::new();
+    let table = db.create_table(name("events"));
+    db.enable_change_feed(table, false).unwrap();
+    for i in 0..MAX_PENDING_CHANGES + 10 {
+        db.create_row(table, Something::Int(i as i32)).unwrap();
+    }
+    let (count, buf) = db.take_changes(0);
+    assert_eq!(count, 1);
+    assert_eq!(buf[8], ChangeKind::Overflowed as u8);
+
+    // the feed starts over once taken
+    db.create_row(table, Something::Int(-1)).unwrap();
+    let (count, buf) = db.take_changes(0);
+    assert_eq!(count, 1);
+    assert_eq!(buf[8], ChangeKind::Created as u8);
+}